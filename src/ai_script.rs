@@ -0,0 +1,373 @@
+use crate::settings::Settings;
+use crate::ship_ai::{AIAction, Condition, ShipAI};
+use crate::structs::{fingerprint, Cords, RelCords, EMPTY_FINGERPRINT};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::time::Duration;
+
+const BUILTIN_SCRIPTS: &str = include_str!("../assets/ai_scripts.json");
+
+//a wait, either a named settings value so it follows the settings panel and difficulty, or a fixed number of ms
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "RawDuration", into = "RawDuration")]
+pub enum DurationRef {
+    FlySpeed,
+    LaserSpeed,
+    Millis(u64),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawDuration {
+    Setting(String),
+    Millis(u64),
+}
+
+impl TryFrom<RawDuration> for DurationRef {
+    type Error = String;
+
+    fn try_from(raw: RawDuration) -> Result<Self, Self::Error> {
+        match raw {
+            RawDuration::Millis(millis) => Ok(DurationRef::Millis(millis)),
+            RawDuration::Setting(name) => match name.as_str() {
+                "fly_speed" => Ok(DurationRef::FlySpeed),
+                "laser_speed" => Ok(DurationRef::LaserSpeed),
+                _ => Err(format!("unknown settings value `{}`, expected fly_speed, laser_speed or a number of ms", name)),
+            },
+        }
+    }
+}
+
+impl From<DurationRef> for RawDuration {
+    fn from(duration: DurationRef) -> Self {
+        match duration {
+            DurationRef::FlySpeed => RawDuration::Setting(String::from("fly_speed")),
+            DurationRef::LaserSpeed => RawDuration::Setting(String::from("laser_speed")),
+            DurationRef::Millis(millis) => RawDuration::Millis(millis),
+        }
+    }
+}
+
+impl DurationRef {
+    fn resolver(&self) -> Box<dyn Fn(&Settings) -> Duration> {
+        match self {
+            DurationRef::FlySpeed => Box::new(|s: &Settings| s.value_stats.fly_speed),
+            DurationRef::LaserSpeed => Box::new(|s: &Settings| s.value_stats.laser_speed),
+            DurationRef::Millis(millis) => {
+                let duration = Duration::from_millis(*millis);
+                Box::new(move |_: &Settings| duration)
+            }
+        }
+    }
+}
+
+//one step of an ai script as written in ai_scripts.json
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptStep {
+    Nothing,
+    Remove,
+    Shoot,
+    RandomShoot,
+    ShootOrNothing,
+    Move(Cords),
+    RelativeMove(RelCords),
+    MoveCautious(RelCords),
+    DiveTowardPlayer,
+    ReturnHome,
+    ApproachHome,
+    TractorBeam,
+    Wait(DurationRef),
+    Await(AwaitStep),
+    If {
+        condition: Condition,
+        #[serde(deserialize_with = "non_empty")]
+        then: Vec<ScriptStep>,
+        #[serde(default, rename = "else")]
+        otherwise: Vec<ScriptStep>,
+    },
+    Repeat {
+        times: NonZeroU32,
+        #[serde(deserialize_with = "non_empty")]
+        steps: Vec<ScriptStep>,
+    },
+    Random(#[serde(deserialize_with = "non_empty_branches")] Vec<Vec<ScriptStep>>),
+    Label(String),
+    Goto(String),
+    Call(String),
+}
+
+impl ScriptStep {
+    fn is_control_flow(&self) -> bool {
+        matches!(
+            self,
+            ScriptStep::If { .. } | ScriptStep::Repeat { .. } | ScriptStep::Random(_) | ScriptStep::Label(_) | ScriptStep::Goto(_) | ScriptStep::Call(_)
+        )
+    }
+}
+
+//waits `after` and then runs `action`, which has to be a single step
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "RawAwaitStep")]
+pub struct AwaitStep {
+    pub after: DurationRef,
+    pub action: Box<ScriptStep>,
+}
+
+#[derive(Deserialize)]
+struct RawAwaitStep {
+    after: DurationRef,
+    action: Box<ScriptStep>,
+}
+
+impl TryFrom<RawAwaitStep> for AwaitStep {
+    type Error = String;
+
+    fn try_from(raw: RawAwaitStep) -> Result<Self, Self::Error> {
+        if raw.action.is_control_flow() {
+            return Err(String::from("await can only wrap a single action, not if, repeat, random, label, goto or call"));
+        }
+        Ok(AwaitStep { after: raw.after, action: raw.action })
+    }
+}
+
+fn non_empty<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<ScriptStep>, D::Error> {
+    let steps = Vec::<ScriptStep>::deserialize(deserializer)?;
+    if steps.is_empty() {
+        return Err(D::Error::custom("expected at least one step"));
+    }
+    Ok(steps)
+}
+
+fn non_empty_branches<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vec<ScriptStep>>, D::Error> {
+    let branches = Vec::<Vec<ScriptStep>>::deserialize(deserializer)?;
+    if branches.is_empty() || branches.iter().any(|branch| branch.is_empty()) {
+        return Err(D::Error::custom("random needs at least one branch and every branch needs at least one step"));
+    }
+    Ok(branches)
+}
+
+#[derive(Deserialize)]
+struct ScriptFile {
+    scripts: HashMap<String, Steps>,
+    #[serde(default)]
+    subroutines: HashMap<String, Steps>,
+}
+
+#[derive(Deserialize)]
+#[serde(transparent)]
+struct Steps(#[serde(deserialize_with = "non_empty")] Vec<ScriptStep>);
+
+//a script together with every subroutine it can call
+#[derive(Clone, Debug)]
+pub struct Script {
+    pub steps: Vec<ScriptStep>,
+    pub subroutines: Arc<HashMap<String, Vec<ScriptStep>>>,
+}
+
+impl Script {
+    pub fn compile(&self) -> ShipAI {
+        let mut names: Vec<&String> = self.subroutines.keys().collect();
+        names.sort();
+
+        let subroutines = names
+            .into_iter()
+            .map(|name| (name.clone(), compile_steps(&self.subroutines[name])))
+            .collect();
+
+        ShipAI::with_subroutines(compile_steps(&self.steps), subroutines)
+    }
+}
+
+fn compile_steps(steps: &[ScriptStep]) -> Vec<AIAction> {
+    steps.iter().map(compile_step).collect()
+}
+
+fn compile_step(step: &ScriptStep) -> AIAction {
+    match step {
+        ScriptStep::Nothing => AIAction::Nothing,
+        ScriptStep::Remove => AIAction::Remove,
+        ScriptStep::Shoot => AIAction::Shoot,
+        ScriptStep::RandomShoot => AIAction::RandomShoot,
+        ScriptStep::ShootOrNothing => AIAction::ShootOrNothing,
+        ScriptStep::Move(cords) => AIAction::Move(*cords),
+        ScriptStep::RelativeMove(rel_cords) => AIAction::RelativeMove(rel_cords.clone()),
+        ScriptStep::MoveCautious(rel_cords) => AIAction::MoveCautious(rel_cords.clone()),
+        ScriptStep::DiveTowardPlayer => AIAction::DiveTowardPlayer,
+        ScriptStep::ReturnHome => AIAction::ReturnHome,
+        ScriptStep::ApproachHome => AIAction::ApproachHome,
+        ScriptStep::TractorBeam => AIAction::TractorBeam,
+        ScriptStep::Wait(duration) => AIAction::AwaitAction(Box::new(AIAction::Nothing), Duration::ZERO, duration.resolver()),
+        ScriptStep::Await(await_step) => AIAction::AwaitAction(
+            Box::new(compile_step(&await_step.action)),
+            Duration::ZERO,
+            await_step.after.resolver(),
+        ),
+        ScriptStep::If { condition, then, otherwise } => {
+            AIAction::If(condition.clone(), compile_steps(then), compile_steps(otherwise))
+        }
+        ScriptStep::Repeat { times, steps } => AIAction::Repeat(times.get(), compile_steps(steps)),
+        ScriptStep::Random(branches) => AIAction::RandomChoice(branches.iter().map(|branch| compile_steps(branch)).collect()),
+        ScriptStep::Label(name) => AIAction::Label(name.clone()),
+        ScriptStep::Goto(name) => AIAction::Goto(name.clone()),
+        ScriptStep::Call(name) => AIAction::Call(name.clone()),
+    }
+}
+
+//collects every label declared in the steps and every goto and call target they use, labels declared twice go in `duplicates`
+fn collect_names<'a>(
+    steps: &'a [ScriptStep],
+    labels: &mut HashSet<&'a str>,
+    duplicates: &mut Vec<&'a str>,
+    gotos: &mut Vec<&'a str>,
+    calls: &mut Vec<&'a str>,
+) {
+    for step in steps {
+        match step {
+            ScriptStep::Label(name) if !labels.insert(name) => duplicates.push(name),
+            ScriptStep::Goto(name) => gotos.push(name),
+            ScriptStep::Call(name) => calls.push(name),
+            ScriptStep::If { then, otherwise, .. } => {
+                collect_names(then, labels, duplicates, gotos, calls);
+                collect_names(otherwise, labels, duplicates, gotos, calls);
+            }
+            ScriptStep::Repeat { steps, .. } => collect_names(steps, labels, duplicates, gotos, calls),
+            ScriptStep::Random(branches) => {
+                for branch in branches {
+                    collect_names(branch, labels, duplicates, gotos, calls);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+//serde can't see across scripts, so labels and subroutines are checked after parsing. every script and subroutine has
+//its own labels, a goto can only jump within the body it is in. bodies are checked sorted by name so the same broken
+//file always reports the same mistake
+fn validate(contents: &str, file: &ScriptFile) -> Result<(), AIScriptError> {
+    let mut scripts: Vec<(&String, &Steps)> = file.scripts.iter().collect();
+    scripts.sort_by_key(|(name, _)| *name);
+    let mut subroutines: Vec<(&String, &Steps)> = file.subroutines.iter().collect();
+    subroutines.sort_by_key(|(name, _)| *name);
+
+    let bodies = scripts
+        .into_iter()
+        .map(|(name, steps)| ("script", name, steps))
+        .chain(subroutines.into_iter().map(|(name, steps)| ("subroutine", name, steps)));
+
+    for (kind, name, steps) in bodies {
+        let mut labels = HashSet::new();
+        let mut duplicates = Vec::new();
+        let mut gotos = Vec::new();
+        let mut calls = Vec::new();
+        collect_names(&steps.0, &mut labels, &mut duplicates, &mut gotos, &mut calls);
+
+        if let Some(label) = duplicates.first() {
+            return Err(AIScriptError::invalid(
+                contents,
+                &format!("\"label\": \"{}\"", label),
+                format!("label `{}` is declared twice in {} `{}`", label, kind, name),
+            ));
+        }
+        if let Some(label) = gotos.iter().find(|label| !labels.contains(*label)) {
+            return Err(AIScriptError::invalid(
+                contents,
+                &format!("\"goto\": \"{}\"", label),
+                format!("goto to unknown label `{}` in {} `{}`", label, kind, name),
+            ));
+        }
+        if let Some(call) = calls.iter().find(|call| !file.subroutines.contains_key(**call)) {
+            return Err(AIScriptError::invalid(
+                contents,
+                &format!("\"call\": \"{}\"", call),
+                format!("call to unknown subroutine `{}` in {} `{}`", call, kind, name),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum AIScriptError {
+    Io(String, std::io::Error),
+    Parse { line: usize, column: usize, message: String },
+    Invalid { line: Option<usize>, message: String },
+}
+
+impl AIScriptError {
+    //points at the first line containing `needle`, good enough to find a bad name in a hand written file
+    fn invalid(contents: &str, needle: &str, message: String) -> Self {
+        let line = contents.lines().position(|line| line.contains(needle)).map(|index| index + 1);
+        AIScriptError::Invalid { line, message }
+    }
+}
+
+impl fmt::Display for AIScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AIScriptError::Io(path, err) => write!(f, "{}: {}", path, err),
+            AIScriptError::Parse { message, .. } => write!(f, "{}", message),
+            AIScriptError::Invalid { line: Some(line), message } => write!(f, "{} at line {}", message, line),
+            AIScriptError::Invalid { line: None, message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<serde_json::Error> for AIScriptError {
+    fn from(err: serde_json::Error) -> Self {
+        AIScriptError::Parse {
+            line: err.line(),
+            column: err.column(),
+            message: err.to_string(),
+        }
+    }
+}
+
+//every named script enemies can use
+#[derive(Clone, Debug)]
+pub struct AIScriptLibrary {
+    scripts: HashMap<String, Script>,
+    fingerprint: u64,
+}
+
+impl AIScriptLibrary {
+    //the scripts shipped in assets/ai_scripts.json
+    pub fn builtin() -> Self {
+        Self::from_json(BUILTIN_SCRIPTS).expect("assets/ai_scripts.json is invalid")
+    }
+
+    pub fn load(path: &str) -> Result<Self, AIScriptError> {
+        let contents = fs::read_to_string(path).map_err(|err| AIScriptError::Io(path.to_string(), err))?;
+        Self::from_json(&contents)
+    }
+
+    pub fn from_json(contents: &str) -> Result<Self, AIScriptError> {
+        let file: ScriptFile = serde_json::from_str(contents)?;
+        validate(contents, &file)?;
+
+        let subroutines = Arc::new(file.subroutines.into_iter().map(|(name, steps)| (name, steps.0)).collect());
+        let scripts = file
+            .scripts
+            .into_iter()
+            .map(|(name, steps)| (name, Script { steps: steps.0, subroutines: Arc::clone(&subroutines) }))
+            .collect();
+
+        Ok(AIScriptLibrary { scripts, fingerprint: fingerprint(EMPTY_FINGERPRINT, contents.as_bytes()) })
+    }
+
+    //changes whenever the scripts file does
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Script> {
+        self.scripts.get(name)
+    }
+}
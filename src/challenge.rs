@@ -0,0 +1,123 @@
+use crate::enemy::{EnemyCatalog, EnemyId};
+use crate::ship::ShipGrid;
+use crate::structs::Cords;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::Arc;
+use std::time::Duration;
+
+//every CHALLENGE_INTERVAL levels, starting at level 3, is a challenging stage
+pub const CHALLENGE_INTERVAL: u32 = 4;
+pub const CHALLENGE_HIT_BONUS: u32 = 100;
+pub const PERFECT_BONUS: u32 = 10000;
+
+const SHIPS_PER_GROUP: u32 = 8;
+const SPAWN_SPACING: Duration = Duration::from_millis(300);
+const RESULTS_DURATION: Duration = Duration::from_secs(3);
+
+//a group of ships entering one after another at `entry` and flying the `path` script
+struct ChallengeGroup {
+    start: Duration,
+    entry: Cords,
+    path: &'static str,
+}
+
+const GROUPS: [ChallengeGroup; 5] = [
+    ChallengeGroup { start: Duration::from_millis(0), entry: Cords(0, 0), path: "challenge_swoop_right" },
+    ChallengeGroup { start: Duration::from_millis(4000), entry: Cords(0, 15), path: "challenge_swoop_left" },
+    ChallengeGroup { start: Duration::from_millis(8000), entry: Cords(0, 3), path: "challenge_loop_right" },
+    ChallengeGroup { start: Duration::from_millis(12000), entry: Cords(0, 12), path: "challenge_loop_left" },
+    ChallengeGroup { start: Duration::from_millis(16000), entry: Cords(0, 0), path: "challenge_swoop_right" },
+];
+
+pub fn is_challenging_stage(level: u32) -> bool {
+    level % CHALLENGE_INTERVAL == CHALLENGE_INTERVAL - 1
+}
+
+//a bonus round, the groups fly across the board without shooting and every kill counts toward the bonus
+pub struct ChallengeStage {
+    elapsed: Duration,
+    spawned: Vec<u32>,
+    group_enemies: Vec<Option<EnemyId>>,
+    total: u32,
+    hits: u32,
+    rng: StdRng,
+}
+
+impl ChallengeStage {
+    pub fn new(seed: u64) -> Self {
+        ChallengeStage {
+            elapsed: Duration::ZERO,
+            spawned: vec![0; GROUPS.len()],
+            group_enemies: vec![None; GROUPS.len()],
+            total: 0,
+            hits: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    //puts every ship that is due on the board, a ship waits if its entry cell is taken
+    pub fn update(&mut self, dt: Duration, ship_grid: &mut ShipGrid, enemies: &Arc<EnemyCatalog>) {
+        self.elapsed += dt;
+
+        for (index, group) in GROUPS.iter().enumerate() {
+            let script = match enemies.named_script(group.path) {
+                Some(script) => script,
+                None => {
+                    self.spawned[index] = SHIPS_PER_GROUP;
+                    continue;
+                }
+            };
+
+            let due = group.start + SPAWN_SPACING * self.spawned[index];
+            if self.spawned[index] >= SHIPS_PER_GROUP || self.elapsed < due || ship_grid.grid.contains_key(&group.entry) {
+                continue;
+            }
+
+            // the whole group is the same enemy, picked when its first ship enters
+            let enemy_id = *self.group_enemies[index].get_or_insert_with(|| enemies.pick(&mut self.rng));
+            ship_grid.grid.insert(group.entry, enemies.spawn_with_script(enemy_id, script));
+            self.spawned[index] += 1;
+            self.total += 1;
+        }
+    }
+
+    pub fn record_hit(&mut self) {
+        self.hits += 1;
+    }
+
+    pub fn is_spawning_done(&self) -> bool {
+        self.spawned.iter().all(|&spawned| spawned >= SHIPS_PER_GROUP)
+    }
+
+    pub fn results(&self) -> ChallengeResults {
+        let perfect = self.total > 0 && self.hits == self.total;
+        let bonus = self.hits * CHALLENGE_HIT_BONUS + if perfect { PERFECT_BONUS } else { 0 };
+
+        ChallengeResults {
+            hits: self.hits,
+            total: self.total,
+            perfect,
+            bonus,
+            remaining: RESULTS_DURATION,
+        }
+    }
+}
+
+//what the results screen after a challenging stage shows
+#[derive(Clone, Debug)]
+pub struct ChallengeResults {
+    pub hits: u32,
+    pub total: u32,
+    pub perfect: bool,
+    pub bonus: u32,
+    remaining: Duration,
+}
+
+impl ChallengeResults {
+    //true once the results have been shown long enough
+    pub fn tick(&mut self, dt: Duration) -> bool {
+        self.remaining = self.remaining.saturating_sub(dt);
+        self.remaining.is_zero()
+    }
+}
@@ -0,0 +1,50 @@
+use crate::ship::{Faction, Ship, ShipKind};
+
+//what happens when a projectile flies into a cell that is already taken
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Collision {
+    //the target takes a hit and the projectile is used up
+    Hit,
+    //the projectile is used up and the target is left alone
+    Absorb,
+    //both projectiles are used up
+    Cancel,
+    //the projectile flies on past the target
+    Pass,
+    //the projectile waits for the cell to clear
+    Block,
+}
+
+//what the fighter does with a ship in one of its cells
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Contact {
+    Collect,
+    Capture,
+    Crash,
+    Ignore,
+}
+
+//the rule table for a projectile running into another ship on the grid
+pub fn projectile_collision(projectile: &dyn Ship, target: &dyn Ship) -> Collision {
+    let faction = projectile.faction();
+    match target.kind() {
+        ShipKind::Enemy(_) if faction == Faction::Player => Collision::Hit,
+        // enemies don't shoot each other down
+        ShipKind::Enemy(_) => Collision::Pass,
+        ShipKind::Projectile(_) if target.faction() == faction => Collision::Block,
+        ShipKind::Projectile(_) => Collision::Cancel,
+        ShipKind::TractorBeam => Collision::Pass,
+        ShipKind::Explosion | ShipKind::PowerUp(_) => Collision::Absorb,
+    }
+}
+
+//the rule table for the fighter flying into, or being flown into by, a ship
+pub fn player_contact(ship: &dyn Ship) -> Contact {
+    match ship.kind() {
+        ShipKind::PowerUp(_) => Contact::Collect,
+        ShipKind::TractorBeam => Contact::Capture,
+        ShipKind::Projectile(_) if ship.faction() == Faction::Player => Contact::Ignore,
+        ShipKind::Enemy(_) | ShipKind::Projectile(_) => Contact::Crash,
+        ShipKind::Explosion => Contact::Ignore,
+    }
+}
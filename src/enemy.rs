@@ -0,0 +1,281 @@
+use crate::ai_script::{AIScriptLibrary, Script};
+use crate::ship::{Ship, ShipKind};
+use crate::ship_ai::{AIContext, ShipAI};
+use crate::structs::{fingerprint, Cords, ShipAction};
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+const BUILTIN_ENEMIES: &str = include_str!("../assets/enemies.json");
+
+//index of an archetype in the EnemyCatalog it was loaded from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EnemyId(pub usize);
+
+//one kind of enemy as declared in enemies.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnemyArchetype {
+    pub name: String,
+    pub sprite: String,
+    //shown once the enemy has taken a hit but is still alive
+    #[serde(default)]
+    pub damaged_sprite: Option<String>,
+    pub score: u32,
+    pub hit_points: u32,
+    pub ai: String,
+    //how often the spawner picks this enemy compared to the others, 0 never spawns it at random
+    #[serde(default = "default_spawn_weight")]
+    pub spawn_weight: u32,
+}
+
+fn default_spawn_weight() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EnemyFile {
+    enemies: Vec<EnemyArchetype>,
+}
+
+#[derive(Debug)]
+pub enum EnemyCatalogError {
+    Io(String, std::io::Error),
+    Parse(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for EnemyCatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnemyCatalogError::Io(path, err) => write!(f, "{}: {}", path, err),
+            EnemyCatalogError::Parse(err) => write!(f, "line {}: {}", err.line(), err),
+            EnemyCatalogError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EnemyCatalog {
+    archetypes: Vec<EnemyArchetype>,
+    scripts: Vec<Script>,
+    library: AIScriptLibrary,
+    fingerprint: u64,
+}
+
+impl EnemyCatalog {
+    //the enemies shipped in assets/enemies.json
+    pub fn builtin(scripts: &AIScriptLibrary) -> Self {
+        Self::from_json(BUILTIN_ENEMIES, scripts).expect("assets/enemies.json is invalid")
+    }
+
+    pub fn load(path: &str, scripts: &AIScriptLibrary) -> Result<Self, EnemyCatalogError> {
+        let contents = fs::read_to_string(path).map_err(|err| EnemyCatalogError::Io(path.to_string(), err))?;
+        Self::from_json(&contents, scripts)
+    }
+
+    pub fn from_json(contents: &str, scripts: &AIScriptLibrary) -> Result<Self, EnemyCatalogError> {
+        let file: EnemyFile = serde_json::from_str(contents).map_err(EnemyCatalogError::Parse)?;
+
+        if file.enemies.is_empty() {
+            return Err(EnemyCatalogError::Invalid(String::from("no enemies declared")));
+        }
+
+        let mut names = HashSet::new();
+        for archetype in &file.enemies {
+            if !names.insert(archetype.name.as_str()) {
+                return Err(EnemyCatalogError::Invalid(format!("enemy {} is declared twice", archetype.name)));
+            }
+            if archetype.hit_points == 0 {
+                return Err(EnemyCatalogError::Invalid(format!("enemy {} needs at least one hit point", archetype.name)));
+            }
+        }
+
+        if file.enemies.iter().all(|archetype| archetype.spawn_weight == 0) {
+            return Err(EnemyCatalogError::Invalid(String::from("at least one enemy needs a spawn_weight above 0")));
+        }
+
+        let mut enemy_scripts = Vec::new();
+        for archetype in &file.enemies {
+            match scripts.get(&archetype.ai) {
+                Some(script) => enemy_scripts.push(script.clone()),
+                None => {
+                    return Err(EnemyCatalogError::Invalid(format!("enemy {} uses unknown ai script {}", archetype.name, archetype.ai)));
+                }
+            }
+        }
+
+        Ok(EnemyCatalog {
+            archetypes: file.enemies,
+            scripts: enemy_scripts,
+            library: scripts.clone(),
+            fingerprint: fingerprint(scripts.fingerprint(), contents.as_bytes()),
+        })
+    }
+
+    //changes whenever the enemies file or the scripts they fly do
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    pub fn len(&self) -> usize {
+        self.archetypes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.archetypes.is_empty()
+    }
+
+    pub fn get(&self, id: EnemyId) -> &EnemyArchetype {
+        &self.archetypes[id.0]
+    }
+
+    pub fn script(&self, id: EnemyId) -> &Script {
+        &self.scripts[id.0]
+    }
+
+    //a random enemy, weighted by spawn_weight
+    pub fn pick(&self, rng: &mut StdRng) -> EnemyId {
+        let total: u32 = self.archetypes.iter().map(|archetype| archetype.spawn_weight).sum();
+        let mut roll = rng.random_range(0..total);
+
+        for (id, archetype) in self.iter() {
+            if roll < archetype.spawn_weight {
+                return id;
+            }
+            roll -= archetype.spawn_weight;
+        }
+        EnemyId(self.archetypes.len() - 1)
+    }
+
+    pub fn find(&self, name: &str) -> Option<EnemyId> {
+        self.archetypes.iter().position(|archetype| archetype.name == name).map(EnemyId)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EnemyId, &EnemyArchetype)> {
+        self.archetypes.iter().enumerate().map(|(index, archetype)| (EnemyId(index), archetype))
+    }
+
+    //any script from the library the catalog was loaded with, for enemies flying something other than their own ai
+    pub fn named_script(&self, name: &str) -> Option<&Script> {
+        self.library.get(name)
+    }
+
+    pub fn spawn(self: &Arc<Self>, id: EnemyId) -> Box<dyn Ship> {
+        Box::new(EnemyShip::new(self.clone(), id))
+    }
+
+    pub fn spawn_with_script(self: &Arc<Self>, id: EnemyId, script: &Script) -> Box<dyn Ship> {
+        Box::new(EnemyShip::with_ai(self.clone(), id, script.compile()))
+    }
+}
+
+//every enemy on the board, its look, points and behaviour all come from its archetype
+pub struct EnemyShip {
+    catalog: Arc<EnemyCatalog>,
+    enemy_id: EnemyId,
+    ai: ShipAI,
+    entry: Option<ShipAI>,
+    hit_points: u32,
+    anchor: Option<Cords>,
+    holds_captive: bool,
+    id: Uuid,
+}
+
+impl EnemyShip {
+    pub fn new(catalog: Arc<EnemyCatalog>, enemy_id: EnemyId) -> Self {
+        let ai = catalog.script(enemy_id).compile();
+        Self::with_ai(catalog, enemy_id, ai)
+    }
+
+    pub fn with_ai(catalog: Arc<EnemyCatalog>, enemy_id: EnemyId, ai: ShipAI) -> Self {
+        let hit_points = catalog.get(enemy_id).hit_points;
+
+        Self {
+            catalog,
+            enemy_id,
+            ai,
+            entry: None,
+            hit_points,
+            anchor: None,
+            holds_captive: false,
+            id: Uuid::new_v4(),
+        }
+    }
+
+    pub fn archetype(&self) -> &EnemyArchetype {
+        self.catalog.get(self.enemy_id)
+    }
+}
+
+impl Ship for EnemyShip {
+    fn kind(&self) -> ShipKind {
+        ShipKind::Enemy(self.enemy_id)
+    }
+
+    fn score_value(&self) -> u32 {
+        self.archetype().score
+    }
+
+    fn hit_points(&self) -> u32 {
+        self.hit_points
+    }
+
+    fn take_hit(&mut self) -> bool {
+        self.hit_points = self.hit_points.saturating_sub(1);
+        self.hit_points == 0
+    }
+
+    fn is_damaged(&self) -> bool {
+        self.hit_points < self.archetype().hit_points
+    }
+
+    fn holds_captive(&self) -> bool {
+        self.holds_captive
+    }
+
+    fn capture_fighter(&mut self) {
+        self.holds_captive = true;
+    }
+
+    fn anchor(&self) -> Option<Cords> {
+        self.anchor
+    }
+
+    fn set_anchor(&mut self, anchor: Cords) {
+        self.anchor = Some(anchor);
+    }
+
+    fn set_entry(&mut self, entry: ShipAI) {
+        self.entry = Some(entry);
+    }
+
+    fn is_entering(&self) -> bool {
+        self.entry.is_some()
+    }
+
+    fn move_interval(&self) -> Option<Duration> {
+        self.entry.as_ref().unwrap_or(&self.ai).interval()
+    }
+
+    fn get_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn get_action(&mut self, cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>, ctx: &AIContext, rng: &mut StdRng) -> ShipAction {
+        // the entry runs once, then the ship's own ai takes over
+        if self.entry.as_ref().is_some_and(|entry| entry.laps() > 0) {
+            self.entry = None;
+        }
+
+        match &mut self.entry {
+            Some(entry) => entry.get_action(cords, game_board, ctx, rng),
+            None => self.ai.get_action(cords, game_board, ctx, rng),
+        }
+    }
+}
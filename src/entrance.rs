@@ -0,0 +1,114 @@
+use crate::enemy::EnemyCatalog;
+use crate::formation::FORMATION_ROWS;
+use crate::ship::{Ship, ShipGrid};
+use crate::structs::{Cords, COLUMNS};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+//how long the "STAGE N" banner stays up before the enemies start flying in
+pub const STAGE_BANNER_DURATION: Duration = Duration::from_millis(2000);
+
+//enemies shot down before they reach their slot are worth this many times their score
+pub const ENTRANCE_SCORE_MULTIPLIER: u32 = 2;
+
+const SHIPS_PER_GROUP: usize = 8;
+const GROUP_SPACING: Duration = Duration::from_millis(1500);
+const SPAWN_SPACING: Duration = Duration::from_millis(150);
+
+//the edge of the board a group flies in from and the path it flies before settling into formation
+struct EntrancePath {
+    entry: Cords,
+    path: &'static str,
+}
+
+//groups take turns entering below the formation from the left and the right
+const PATHS: [EntrancePath; 2] = [
+    EntrancePath { entry: Cords(FORMATION_ROWS + 1, 0), path: "entrance_left" },
+    EntrancePath { entry: Cords(FORMATION_ROWS + 1, COLUMNS - 1), path: "entrance_right" },
+];
+
+struct EntranceGroup {
+    start: Duration,
+    entry: Cords,
+    //ships without a path to fly are put straight into their slot
+    direct: bool,
+    ships: VecDeque<(Cords, Box<dyn Ship>)>,
+    spawned: u32,
+}
+
+//a new level's ships waiting to fly in, a group at a time
+pub struct Entrance {
+    elapsed: Duration,
+    groups: Vec<EntranceGroup>,
+}
+
+impl Entrance {
+    //groups are made from the top rows down so later groups never have to fly through ships already in their slots.
+    //`entry` is the level's own entrance script and replaces the builtin paths
+    pub fn new(wave: HashMap<Cords, Box<dyn Ship>>, enemies: &EnemyCatalog, entry: Option<&str>) -> Self {
+        let mut ships: Vec<(Cords, Box<dyn Ship>)> = wave.into_iter().collect();
+        ships.sort_by_key(|(cords, _)| *cords);
+
+        let mut groups = Vec::new();
+        let mut ships = ships.into_iter().peekable();
+        while ships.peek().is_some() {
+            let index = groups.len();
+            let side = &PATHS[index % PATHS.len()];
+            let script = enemies.named_script(entry.unwrap_or(side.path));
+
+            let ships = ships
+                .by_ref()
+                .take(SHIPS_PER_GROUP)
+                .map(|(cords, mut ship)| {
+                    if let Some(script) = script {
+                        ship.set_entry(script.compile());
+                    }
+                    (cords, ship)
+                })
+                .collect();
+
+            groups.push(EntranceGroup {
+                start: GROUP_SPACING * index as u32,
+                entry: side.entry,
+                direct: script.is_none(),
+                ships,
+                spawned: 0,
+            });
+        }
+
+        Entrance {
+            elapsed: Duration::ZERO,
+            groups,
+        }
+    }
+
+    //puts every ship that is due on the board, a ship waits if the cell it enters at is taken
+    pub fn update(&mut self, dt: Duration, ship_grid: &mut ShipGrid) {
+        self.elapsed += dt;
+
+        for group in &mut self.groups {
+            let due = group.start + SPAWN_SPACING * group.spawned;
+            if self.elapsed < due {
+                continue;
+            }
+
+            let cords = match group.ships.front() {
+                Some((anchor, _)) if group.direct => *anchor,
+                Some(_) => group.entry,
+                None => continue,
+            };
+            if ship_grid.grid.contains_key(&cords) {
+                continue;
+            }
+
+            if let Some((_, ship)) = group.ships.pop_front() {
+                ship_grid.grid.insert(cords, ship);
+                group.spawned += 1;
+            }
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.groups.iter().all(|group| group.ships.is_empty())
+    }
+}
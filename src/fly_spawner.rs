@@ -1,97 +1,167 @@
+use crate::challenge::is_challenging_stage;
+use crate::enemy::{EnemyCatalog, EnemyId};
+use crate::formation::FormationPattern;
+use crate::level::{FormationLayout, LevelDefinition, LevelLibrary, LevelRules};
 use crate::settings::Settings;
-use crate::ship::{new_b2_fly_ship, new_fly_ship, new_northrop_fly_ship, new_tiki_fly_ship, Ship};
-use crate::structs::{Cords, COLUMNS};
+use crate::ship::Ship;
+use crate::structs::{stream_seed, Cords, RngStream};
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 pub struct FlySpawner {
-    cols: usize,
-    settings: Settings,
     pub current_level: u32,
     pub flies_per_level_base: u32,
+    seed: u64,
     rng: StdRng,
+    enemies: Arc<EnemyCatalog>,
+    levels: Arc<LevelLibrary>,
 }
 
 impl FlySpawner {
-    pub fn new() -> Self {
+    pub fn new(seed: u64, enemies: Arc<EnemyCatalog>, levels: Arc<LevelLibrary>) -> Self {
+        let seed = stream_seed(seed, RngStream::Spawner);
         FlySpawner {
-            cols: COLUMNS,
-            settings: Settings::new(),
             current_level: 1,
             flies_per_level_base: 10, // Doubled from 5 to 10
-            rng: StdRng::seed_from_u64(42),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            enemies,
+            levels,
+        }
+    }
+
+    //the authored definition of the current level, None past the end of the level file
+    pub fn level(&self) -> Option<&LevelDefinition> {
+        self.levels.get(self.current_level)
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.level().map_or(1.0, |level| level.speed)
+    }
+
+    pub fn rules(&self) -> LevelRules {
+        self.level().map(|level| level.rules).unwrap_or_default()
+    }
+
+    //authored levels say so themselves, procedural ones follow the challenge interval
+    pub fn is_challenge_level(&self, level: u32) -> bool {
+        match self.levels.get(level) {
+            Some(definition) => definition.rules.challenge,
+            None => is_challenging_stage(level),
         }
     }
 
     pub fn spawn_flies(&mut self, fly_count: u32) -> HashMap<Cords, Box<dyn Ship>> {
         let mut grid = HashMap::new();
-        // Always use wave formation regardless of level
-        self.create_wave_formation(&mut grid, fly_count);
+
+        let cells = match self.level().and_then(|level| level.formation.clone()) {
+            Some(FormationLayout::Cells { cells }) => cells.into_iter().take(fly_count as usize).collect(),
+            Some(FormationLayout::Pattern(name)) => {
+                let pattern = FormationPattern::from_name(&name).unwrap_or(FormationPattern::Wave);
+                pattern.layout(fly_count as usize, &mut self.rng)
+            }
+            None => FormationPattern::for_level(self.current_level, &mut self.rng).layout(fly_count as usize, &mut self.rng),
+        };
+
+        for cords in cells {
+            self.place_ship(&mut grid, cords);
+        }
+
         grid
     }
 
     fn get_random_ship(&mut self) -> Box<dyn Ship> {
-        // Randomly choose a ship type
-        match self.rng.gen_range(0..4) {
-            0 => new_fly_ship(),
-            1 => new_tiki_fly_ship(),
-            2 => new_northrop_fly_ship(),
-            _ => new_b2_fly_ship(),
-        }
+        let mix: Vec<(EnemyId, u32)> = self.level()
+            .map(|level| {
+                level.enemies
+                    .iter()
+                    .filter_map(|(name, &weight)| Some((self.enemies.find(name)?, weight)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // Randomly choose a ship type, from the level's mix when it has one
+        let enemy_id = match self.pick_from_mix(&mix) {
+            Some(enemy_id) => enemy_id,
+            None => self.enemies.pick(&mut self.rng),
+        };
+
+        self.enemies.spawn(enemy_id)
     }
 
-    fn create_wave_formation(&mut self, grid: &mut HashMap<Cords, Box<dyn Ship>>, fly_count: u32) {
-        let mut flies_placed = 0;
-        let max_rows = 5;
+    fn pick_from_mix(&mut self, mix: &[(EnemyId, u32)]) -> Option<EnemyId> {
+        let total: u32 = mix.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return None;
+        }
 
-        // Fill the wave pattern with random ships
-        for row in 0..max_rows {
-            for col in (row % 3 + 2..self.cols - 2).step_by(3) {
-                if flies_placed >= fly_count { return; }
-                grid.insert(Cords(row, col), self.get_random_ship());
-                flies_placed += 1;
+        let mut roll = self.rng.random_range(0..total);
+        for &(enemy_id, weight) in mix {
+            if roll < weight {
+                return Some(enemy_id);
             }
+            roll -= weight;
         }
+        None
+    }
 
-        // If we still need more flies, fill in additional positions
-        let mut row = 0;
-        let mut col = 0;
+    //every spawned enemy remembers the cell it was placed in as its formation anchor
+    fn place_ship(&mut self, grid: &mut HashMap<Cords, Box<dyn Ship>>, cords: Cords) {
+        let mut ship = self.get_random_ship();
+        ship.set_anchor(cords);
+        grid.insert(cords, ship);
+    }
 
-        while flies_placed < fly_count {
-            // Skip positions that are already filled
-            if !grid.contains_key(&Cords(row, col)) {
-                grid.insert(Cords(row, col), self.get_random_ship());
-                flies_placed += 1;
-            }
+    //the script the current level's enemies fly in along instead of the builtin entrance paths
+    pub fn entry_script(&self) -> Option<&str> {
+        self.level().and_then(|level| level.entry.as_deref())
+    }
 
-            // Move to next position
-            col += 2;
-            if col >= self.cols {
-                col = 0;
-                row = (row + 1) % max_rows;
-            }
-        }
+    pub fn spawn_next_level(&mut self, settings: &Settings) -> HashMap<Cords, Box<dyn Ship>> {
+        self.advance_level();
+
+        let fly_count = self.get_current_fly_count(settings);
+        self.spawn_flies(fly_count)
     }
 
-    pub fn spawn_next_level(&mut self) -> HashMap<Cords, Box<dyn Ship>> {
+    //moves on a level without spawning anything, returns the seed for the new level
+    pub fn advance_level(&mut self) -> u64 {
         self.current_level += 1;
 
-        let seed = 42 + self.current_level as u64;
+        let seed = self.seed.wrapping_add(self.current_level as u64);
         self.rng = StdRng::seed_from_u64(seed);
-
-        // Double the fly count from the original calculation
-        let fly_count = (self.flies_per_level_base + self.current_level) * 2;
-        self.spawn_flies(fly_count)
+        seed
     }
 
     pub fn reset_level(&mut self) {
         self.current_level = 1;
-        self.rng = StdRng::seed_from_u64(42);
+        self.rng = StdRng::seed_from_u64(self.seed);
     }
 
-    pub fn get_current_fly_count(&self) -> u32 {
+    pub fn get_current_fly_count(&self, settings: &Settings) -> u32 {
+        if let Some(fly_count) = self.authored_fly_count(settings) {
+            return fly_count;
+        }
+
         // Double the fly count from the original calculation
-        (self.flies_per_level_base + self.current_level) * 2
+        let fly_count = (self.flies_per_level_base + self.current_level) * 2;
+        Self::scale_fly_count(fly_count, settings)
+    }
+
+    //the count the level file asks for, or the number of cells it lists
+    pub fn authored_fly_count(&self, settings: &Settings) -> Option<u32> {
+        let level = self.level()?;
+        let fly_count = match (&level.count, &level.formation) {
+            (Some(count), _) => *count,
+            (None, Some(FormationLayout::Cells { cells })) => cells.len() as u32,
+            (None, _) => return None,
+        };
+        Some(Self::scale_fly_count(fly_count, settings))
+    }
+
+    fn scale_fly_count(fly_count: u32, settings: &Settings) -> u32 {
+        ((fly_count as f32 * settings.value_stats.fly_count_multiplier).round() as u32).max(1)
     }
 }
\ No newline at end of file
@@ -0,0 +1,220 @@
+use crate::structs::{Cords, COLUMNS};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashSet;
+use std::time::Duration;
+
+//patterns are laid out in the top rows so there is room left to dive at the player
+pub const FORMATION_ROWS: usize = 5;
+
+//column offsets the whole formation steps through, one per formation step
+const SWAY: [i32; 8] = [0, 1, 2, 1, 0, -1, -2, -1];
+
+//where every enemy's anchor currently is, the formation sways left and right and every other sway spreads out from the center
+#[derive(Clone, Debug)]
+pub struct Formation {
+    min_column: usize,
+    max_column: usize,
+    center: usize,
+    phase: usize,
+    elapsed: Duration,
+}
+
+impl Formation {
+    pub fn new(anchors: impl IntoIterator<Item = Cords>) -> Self {
+        let columns: Vec<usize> = anchors.into_iter().map(|anchor| anchor.1).collect();
+        let min_column = columns.iter().copied().min().unwrap_or(0);
+        let max_column = columns.iter().copied().max().unwrap_or(COLUMNS - 1);
+
+        Formation {
+            min_column,
+            max_column,
+            center: (min_column + max_column) / 2,
+            phase: 0,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    //the cell the ship anchored at `anchor` belongs in right now
+    pub fn slot(&self, anchor: Cords) -> Cords {
+        self.slot_at(anchor, self.phase)
+    }
+
+    //the cell `anchor` will belong in after the next step
+    pub fn next_slot(&self, anchor: Cords) -> Cords {
+        self.slot_at(anchor, self.phase + 1)
+    }
+
+    //true once `interval` has passed since the last step
+    pub fn tick(&mut self, dt: Duration, interval: Duration) -> bool {
+        self.elapsed += dt;
+        self.elapsed >= interval
+    }
+
+    pub fn advance(&mut self) {
+        self.phase += 1;
+        self.elapsed = Duration::ZERO;
+    }
+
+    fn slot_at(&self, anchor: Cords, phase: usize) -> Cords {
+        let column = anchor.1 as i32 + self.spread(anchor.1, phase) + self.sway(phase);
+        Cords(anchor.0, column as usize)
+    }
+
+    //only breathes when there is a free column on both sides to breathe into
+    fn breathe(&self, phase: usize) -> i32 {
+        let has_room = self.min_column > 0 && self.max_column + 1 < COLUMNS;
+        if has_room && (phase / SWAY.len()) % 2 == 1 { 1 } else { 0 }
+    }
+
+    fn spread(&self, column: usize, phase: usize) -> i32 {
+        match column.cmp(&self.center) {
+            std::cmp::Ordering::Less => -self.breathe(phase),
+            std::cmp::Ordering::Greater => self.breathe(phase),
+            std::cmp::Ordering::Equal => 0,
+        }
+    }
+
+    //clamped so the outermost ships stay on the board
+    fn sway(&self, phase: usize) -> i32 {
+        let left = self.min_column as i32 + self.spread(self.min_column, phase);
+        let right = self.max_column as i32 + self.spread(self.max_column, phase);
+        SWAY[phase % SWAY.len()].clamp(-left, COLUMNS as i32 - 1 - right)
+    }
+}
+
+//the shapes a level's enemies can be laid out in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormationPattern {
+    Wave,
+    VShape,
+    Diamond,
+    Checkerboard,
+    Columns,
+    Ring,
+    Arrow,
+    Scatter,
+}
+
+impl FormationPattern {
+    pub const ALL: [FormationPattern; 8] = [
+        FormationPattern::Wave,
+        FormationPattern::VShape,
+        FormationPattern::Diamond,
+        FormationPattern::Checkerboard,
+        FormationPattern::Columns,
+        FormationPattern::Ring,
+        FormationPattern::Arrow,
+        FormationPattern::Scatter,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FormationPattern::Wave => "wave",
+            FormationPattern::VShape => "v_shape",
+            FormationPattern::Diamond => "diamond",
+            FormationPattern::Checkerboard => "checkerboard",
+            FormationPattern::Columns => "columns",
+            FormationPattern::Ring => "ring",
+            FormationPattern::Arrow => "arrow",
+            FormationPattern::Scatter => "scatter",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|pattern| pattern.name() == name)
+    }
+
+    //procedural levels unlock another pattern every other level and pick one of the unlocked ones
+    pub fn for_level(level: u32, rng: &mut StdRng) -> Self {
+        let unlocked = (level as usize).div_ceil(2).clamp(1, Self::ALL.len());
+        Self::ALL[rng.random_range(0..unlocked)]
+    }
+
+    //cells for up to `count` ships, the pattern's own cells first and then the rest of the formation rows,
+    //so a layout never leaves the formation rows and never has more ships than there are cells
+    pub fn layout(self, count: usize, rng: &mut StdRng) -> Vec<Cords> {
+        let mut taken = HashSet::new();
+        let mut cells = Vec::new();
+
+        for cell in self.cells(rng).into_iter().chain(fill_order()) {
+            if cells.len() >= count {
+                break;
+            }
+            if cell.0 < FORMATION_ROWS && cell.1 < COLUMNS && taken.insert(cell) {
+                cells.push(cell);
+            }
+        }
+
+        cells
+    }
+
+    //the pattern's cells in the order they get filled, so a pattern with fewer ships than cells still keeps its shape
+    fn cells(self, rng: &mut StdRng) -> Vec<Cords> {
+        let last_row = FORMATION_ROWS - 1;
+
+        match self {
+            FormationPattern::Wave => (0..FORMATION_ROWS)
+                .flat_map(|row| (row % 3 + 2..COLUMNS - 2).step_by(3).map(move |col| Cords(row, col)))
+                .collect(),
+            //the point of the v is at the bottom, later ships thicken the arms
+            FormationPattern::VShape => ranked(|row, col| {
+                let arm = (last_row - row) * 3 / 2;
+                Some(column_distance(col).abs_diff(arm) as u32)
+            }),
+            FormationPattern::Diamond => ranked(|row, col| Some((column_distance(col) + row_distance(row) * 2) as u32)),
+            FormationPattern::Checkerboard => ranked(|row, col| {
+                ((row + col) % 2 == 0).then_some(column_distance(col) as u32)
+            }),
+            //whole columns one after another, starting in the middle
+            FormationPattern::Columns => ranked(|row, col| {
+                let distance = column_distance(col);
+                distance.is_multiple_of(3).then_some((distance * FORMATION_ROWS + row) as u32)
+            }),
+            FormationPattern::Ring => ranked(|row, col| {
+                let x = column_distance(col) as f32 / (COLUMNS / 2 - 1) as f32;
+                let y = row_distance(row) as f32 / (FORMATION_ROWS / 2) as f32;
+                Some(((x.hypot(y) - 1.0).abs() * 10.0).round() as u32)
+            }),
+            //a shaft down the middle with the head in the bottom rows pointing at the player
+            FormationPattern::Arrow => ranked(|row, col| {
+                let distance = column_distance(col);
+                let head = if row * 2 >= last_row { distance.abs_diff(last_row - row) } else { distance };
+                Some(head.min(distance) as u32)
+            }),
+            FormationPattern::Scatter => {
+                let mut cells: Vec<Cords> = fill_order().collect();
+                cells.shuffle(rng);
+                cells
+            }
+        }
+    }
+}
+
+//every formation cell, every other column first
+fn fill_order() -> impl Iterator<Item = Cords> {
+    (0..2).flat_map(|offset| {
+        (0..FORMATION_ROWS).flat_map(move |row| (offset..COLUMNS).step_by(2).map(move |col| Cords(row, col)))
+    })
+}
+
+//formation cells sorted by rank, cells without one are left for the fill
+//ties go to mirrored pairs from the middle out so a half filled pattern stays symmetric
+fn ranked(rank: impl Fn(usize, usize) -> Option<u32>) -> Vec<Cords> {
+    let mut cells: Vec<(u32, usize, usize, Cords)> = (0..FORMATION_ROWS)
+        .flat_map(|row| (0..COLUMNS).map(move |col| Cords(row, col)))
+        .filter_map(|cords| Some((rank(cords.0, cords.1)?, cords.0, column_distance(cords.1), cords)))
+        .collect();
+    cells.sort();
+    cells.into_iter().map(|(_, _, _, cords)| cords).collect()
+}
+
+//how many columns away from the middle two columns
+fn column_distance(col: usize) -> usize {
+    (2 * col).abs_diff(COLUMNS - 1) / 2
+}
+
+fn row_distance(row: usize) -> usize {
+    (2 * row).abs_diff(FORMATION_ROWS - 1) / 2
+}
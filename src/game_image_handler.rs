@@ -1,38 +1,161 @@
 use rust_on_rails::prelude::*;
+use crate::enemy::{EnemyCatalog, EnemyId};
+use crate::power_up::PowerUpKind;
+use crate::ship::{Faction, Heading, ProjectileKind};
+use image::RgbaImage;
+use std::collections::HashMap;
+
+//projectile sprites are recoloured from a copy this size, a shot is never drawn bigger than a cell
+const PROJECTILE_SPRITE_SIZE: u32 = 256;
 
 #[derive(Clone)]
 pub struct GameImageHandler {
-    pub fly: ImageKey,
-    pub tiki_fly: ImageKey,
-    pub northrop_fly: ImageKey,
-    pub b2_fly: ImageKey,
+    pub enemies: Vec<ImageKey>,
+    pub damaged_enemies: Vec<Option<ImageKey>>,
     pub explosion: ImageKey,
-    pub bullet_downward: ImageKey,
-    pub bullet_upward: ImageKey,
+    pub tractor_beam: ImageKey,
+    pub projectiles: HashMap<(ProjectileKind, Faction, Heading), ImageKey>,
     pub player: ImageKey,
+    pub power_ups: Vec<ImageKey>,
 }
 
 impl GameImageHandler {
-    pub fn new(ctx: &mut Context) -> Self {
-        let fly = ctx.add_image(image::load_from_memory(include_bytes!("../assets/images/fly.png")).unwrap().into());
+    pub fn new(ctx: &mut Context, enemy_catalog: &EnemyCatalog) -> Self {
         let explosion = ctx.add_image(image::load_from_memory(include_bytes!("../assets/images/explosion.png")).unwrap().into());
-        let bullet_downward = ctx.add_image(image::load_from_memory(include_bytes!("../assets/images/bullet_downward.png")).unwrap().into());
-        let bullet_upward = ctx.add_image(image::load_from_memory(include_bytes!("../assets/images/bullet_upward.png")).unwrap().into());
+        let projectiles = Self::load_projectiles(ctx);
         let player = ctx.add_image(image::load_from_memory(include_bytes!("../assets/images/spaceship.png")).unwrap().into());
+        let tractor_beam = ctx.add_image(image::load_from_memory(include_bytes!("../assets/images/tractor_beam.png")).unwrap().into());
+
+        let power_ups = PowerUpKind::ALL
+            .iter()
+            .map(|&power_up| ctx.add_image(image::load_from_memory(Self::power_up_sprite(power_up)).unwrap().into()))
+            .collect();
 
-        let tiki_fly = ctx.add_image(image::load_from_memory(include_bytes!("../assets/images/tiki_fly.png")).unwrap().into());
-        let northrop_fly = ctx.add_image(image::load_from_memory(include_bytes!("../assets/images/northrop.png")).unwrap().into());
-        let b2_fly = ctx.add_image(image::load_from_memory(include_bytes!("../assets/images/b-2.png")).unwrap().into());
+        let enemies = enemy_catalog
+            .iter()
+            .map(|(_, archetype)| Self::load_sprite(ctx, &archetype.sprite))
+            .collect();
+
+        let damaged_enemies = enemy_catalog
+            .iter()
+            .map(|(_, archetype)| archetype.damaged_sprite.as_ref().map(|sprite| Self::load_sprite(ctx, sprite)))
+            .collect();
 
         Self {
-            fly,
-            tiki_fly,
-            northrop_fly,
-            b2_fly,
+            enemies,
+            damaged_enemies,
             explosion,
-            bullet_downward,
-            bullet_upward,
+            tractor_beam,
+            projectiles,
             player,
+            power_ups,
+        }
+    }
+
+    //the upward sprite for shots flying up and the downward one for shots flying down, coloured by who fired it
+    pub fn projectile(&self, projectile: ProjectileKind, faction: Faction, heading: Heading) -> ImageKey {
+        let faction = if faction == Faction::Player { Faction::Player } else { Faction::Enemy };
+        self.projectiles[&(projectile, faction, heading)]
+    }
+
+    fn load_projectiles(ctx: &mut Context) -> HashMap<(ProjectileKind, Faction, Heading), ImageKey> {
+        let upward = image::load_from_memory(include_bytes!("../assets/images/bullet_upward.png")).unwrap()
+            .thumbnail(PROJECTILE_SPRITE_SIZE, PROJECTILE_SPRITE_SIZE)
+            .to_rgba8();
+        let downward = image::load_from_memory(include_bytes!("../assets/images/bullet_downward.png")).unwrap()
+            .thumbnail(PROJECTILE_SPRITE_SIZE, PROJECTILE_SPRITE_SIZE)
+            .to_rgba8();
+
+        let mut projectiles = HashMap::new();
+        for projectile in ProjectileKind::ALL {
+            for faction in [Faction::Player, Faction::Enemy] {
+                for (heading, sprite) in [(Heading::Up, &upward), (Heading::Down, &downward)] {
+                    let sprite = match Self::projectile_tint(projectile, faction) {
+                        Some(tint) => Self::tint(sprite, tint),
+                        None => sprite.clone(),
+                    };
+                    projectiles.insert((projectile, faction, heading), ctx.add_image(sprite));
+                }
+            }
+        }
+        projectiles
+    }
+
+    //player shots are drawn in cool colours and enemy shots in warm ones so they read apart at a glance
+    fn projectile_tint(projectile: ProjectileKind, faction: Faction) -> Option<[u8; 3]> {
+        match (projectile, faction) {
+            (ProjectileKind::Bullet, Faction::Player) => Some([120, 220, 255]),
+            (ProjectileKind::Spread, Faction::Player) => Some([150, 255, 140]),
+            (ProjectileKind::Bullet, _) => None,
+            (ProjectileKind::Spread, _) => Some([255, 110, 230]),
+        }
+    }
+
+    //recolours a sprite by how bright each pixel is, keeping its shape and transparency
+    fn tint(sprite: &RgbaImage, tint: [u8; 3]) -> RgbaImage {
+        let mut tinted = sprite.clone();
+        for pixel in tinted.pixels_mut() {
+            let brightness = pixel[0].max(pixel[1]).max(pixel[2]) as u32;
+            for channel in 0..3 {
+                pixel[channel] = (brightness * tint[channel] as u32 / 255) as u8;
+            }
+        }
+        tinted
+    }
+
+    pub fn power_up(&self, power_up: PowerUpKind) -> ImageKey {
+        self.power_ups[power_up as usize]
+    }
+
+    fn power_up_sprite(power_up: PowerUpKind) -> &'static [u8] {
+        match power_up {
+            PowerUpKind::RapidFire => include_bytes!("../assets/images/power_up_rapid_fire.png"),
+            PowerUpKind::SpreadShot => include_bytes!("../assets/images/power_up_spread_shot.png"),
+            PowerUpKind::Shield => include_bytes!("../assets/images/power_up_shield.png"),
+            PowerUpKind::ExtraLife => include_bytes!("../assets/images/power_up_extra_life.png"),
+            PowerUpKind::ScoreMultiplier => include_bytes!("../assets/images/power_up_score_multiplier.png"),
+        }
+    }
+
+    pub fn enemy(&self, id: EnemyId) -> ImageKey {
+        self.enemies[id.0]
+    }
+
+    //falls back to the normal sprite for enemies without a damaged one
+    pub fn damaged_enemy(&self, id: EnemyId) -> ImageKey {
+        self.damaged_enemies[id.0].unwrap_or(self.enemies[id.0])
+    }
+
+    //sprites that ship with the game are embedded, anything else is read from disk
+    fn load_sprite(ctx: &mut Context, sprite: &str) -> ImageKey {
+        let image = match Self::embedded_sprite(sprite) {
+            Some(bytes) => image::load_from_memory(bytes).ok(),
+            None => image::open(sprite).ok(),
+        };
+
+        let image = image.unwrap_or_else(|| {
+            eprintln!("Failed to load sprite {}, using the fly sprite instead", sprite);
+            image::load_from_memory(include_bytes!("../assets/images/fly.png")).unwrap()
+        });
+
+        ctx.add_image(image.into())
+    }
+
+    fn embedded_sprite(sprite: &str) -> Option<&'static [u8]> {
+        match sprite {
+            "fly.png" => Some(include_bytes!("../assets/images/fly.png")),
+            "tiki_fly.png" => Some(include_bytes!("../assets/images/tiki_fly.png")),
+            "northrop.png" => Some(include_bytes!("../assets/images/northrop.png")),
+            "b-2.png" => Some(include_bytes!("../assets/images/b-2.png")),
+            "b-2_damaged.png" => Some(include_bytes!("../assets/images/b-2_damaged.png")),
+            "boss.png" => Some(include_bytes!("../assets/images/boss.png")),
+            "boss_damaged.png" => Some(include_bytes!("../assets/images/boss_damaged.png")),
+            "tractor_beam.png" => Some(include_bytes!("../assets/images/tractor_beam.png")),
+            "explosion.png" => Some(include_bytes!("../assets/images/explosion.png")),
+            "bullet_downward.png" => Some(include_bytes!("../assets/images/bullet_downward.png")),
+            "bullet_upward.png" => Some(include_bytes!("../assets/images/bullet_upward.png")),
+            "spaceship.png" => Some(include_bytes!("../assets/images/spaceship.png")),
+            _ => None,
         }
     }
 }
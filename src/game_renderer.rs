@@ -1,51 +1,290 @@
 use crate::structs::Cords;
 use crate::player::Player;
-use crate::ship::Ship;
+use crate::physics::{PhysicsWorld, Vec2};
+use crate::ship::{Heading, Ship, ShipKind};
 use crate::game_image_handler::GameImageHandler;
+use crate::server::ServerStatus;
+use crate::challenge::ChallengeResults;
+use crate::power_up::{PowerUpKind, MAX_LIVES};
 use rust_on_rails::prelude::*;
 use std::collections::HashMap;
-use crate::structs::{MARGIN, ROWS, START_X, START_Y, CELL_SIZE};
+use std::time::Duration;
+use uuid::Uuid;
+use crate::structs::{MARGIN, START_X, START_Y, CELL_SIZE};
+use crate::layout::{Layout, BOARD_CENTER_Y, BOARD_WIDTH, INFO_Y, LIVES_Y};
 
-pub struct GameRenderer;
+const HIT_FLASH_DURATION: Duration = Duration::from_millis(120);
+//moves further than this many cells, like a dive wrapping back to the top, jump instead of gliding
+const MAX_GLIDE_DISTANCE: f32 = 2.0;
+
+//a ship sliding from where it was drawn toward the cell it moved to, positions are (row, column) and fractional mid glide
+struct Glide {
+    from: (f32, f32),
+    to: Cords,
+    elapsed: Duration,
+    duration: Duration,
+    interval: Duration,  // the ship's move timer, how long the glide is remembered to measure the next move against
+}
+
+impl Glide {
+    fn position(&self) -> (f32, f32) {
+        let progress = if self.duration.is_zero() {
+            1.0
+        } else {
+            (self.elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+        };
+        (
+            self.from.0 + (self.to.0 as f32 - self.from.0) * progress,
+            self.from.1 + (self.to.1 as f32 - self.from.1) * progress,
+        )
+    }
+}
+
+//text is drawn from a &'static str, so a label that only changes now and then is leaked once per value instead of every frame
+struct CachedText<K> {
+    key: Option<K>,
+    text: &'static str,
+}
+
+impl<K: PartialEq> CachedText<K> {
+    fn get(&mut self, key: K, build: impl FnOnce(&K) -> String) -> &'static str {
+        if self.key.as_ref() != Some(&key) {
+            self.text = build(&key).leak();
+            self.key = Some(key);
+        }
+        self.text
+    }
+}
+
+impl<K> Default for CachedText<K> {
+    fn default() -> Self {
+        CachedText { key: None, text: "" }
+    }
+}
+
+pub struct GameRenderer {
+    hit_flashes: HashMap<Uuid, Duration>,  // ships that were just hit and how long they still flash
+    glides: HashMap<Uuid, Glide>,
+    layout: Layout,
+    status_text: CachedText<ServerStatus>,
+    seed_text: CachedText<(u64, bool)>,
+    hits_text: CachedText<(u32, u32)>,
+    bonus_text: CachedText<(bool, u32)>,
+    banner_text: CachedText<u32>,
+    power_up_texts: HashMap<PowerUpKind, CachedText<u32>>,  // the whole seconds left shown next to each running power-up
+    weapon_text: CachedText<String>,
+}
 
 //GameRenderer returns canvas items like the fly bullets ETC this gets called in Lib.rs.
 
 impl GameRenderer {
     pub fn new() -> Self {
-        GameRenderer
+        GameRenderer {
+            hit_flashes: HashMap::new(),
+            glides: HashMap::new(),
+            layout: Layout::default(),
+            status_text: CachedText::default(),
+            seed_text: CachedText::default(),
+            hits_text: CachedText::default(),
+            bonus_text: CachedText::default(),
+            banner_text: CachedText::default(),
+            power_up_texts: HashMap::new(),
+            weapon_text: CachedText::default(),
+        }
+    }
+
+    //the window changed size, everything is drawn scaled to the new one from the next frame
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+
+    pub fn flash(&mut self, ship_id: Uuid) {
+        self.hit_flashes.insert(ship_id, HIT_FLASH_DURATION);
+    }
+
+    pub fn tick(&mut self, dt: Duration) {
+        for remaining in self.hit_flashes.values_mut() {
+            *remaining = remaining.saturating_sub(dt);
+        }
+        self.hit_flashes.retain(|_, remaining| !remaining.is_zero());
+
+        for glide in self.glides.values_mut() {
+            glide.elapsed += dt;
+        }
+        self.glides.retain(|_, glide| glide.elapsed < glide.duration.max(glide.interval));
+    }
+
+    //slides the ship from `from` to `to` over its move timer, the grid has already put it in `to`
+    pub fn glide(&mut self, ship_id: Uuid, from: Cords, to: Cords, interval: Duration) {
+        let (start, duration) = match self.glides.get(&ship_id) {
+            // moving again before the last glide is remembered away, carry on from where it is drawn at the pace it really moves
+            Some(glide) if glide.to == from => (glide.position(), glide.elapsed.min(interval)),
+            _ => ((from.0 as f32, from.1 as f32), interval),
+        };
+
+        let distance = (to.0 as f32 - start.0).abs().max((to.1 as f32 - start.1).abs());
+        if distance > MAX_GLIDE_DISTANCE {
+            self.glides.remove(&ship_id);
+            return;
+        }
+
+        self.glides.insert(ship_id, Glide {
+            from: start,
+            to,
+            elapsed: Duration::ZERO,
+            duration,
+            interval,
+        });
     }
 
     pub fn draw(
         &self,
         ctx: &mut Context,
         grid: &HashMap<Cords, Box<dyn Ship>>,
+        physics: Option<&PhysicsWorld>,
         player: &Player,
         image_handler: &GameImageHandler,
         score: u32,
         font: FontKey,
     ) {
-        let canvas_items = self.get_game_canvas_items(grid, player, image_handler);
+        let canvas_items = self.get_game_canvas_items(grid, physics, player, image_handler);
         for item in canvas_items {
             ctx.draw(item);
         }
 
-        ctx.draw(CanvasItem::Text(
-            Area((20, 20), None),
-            Text::new(
-                format!("Score: {}", score).leak(),
-                "FF0000",
-                255,
-                Some(800),
-                25,
-                30,
-                font
-            )
+        ctx.draw(self.layout.text_at(
+            (20, 20),
+            self.layout.text(format!("Score: {}", score).leak(), "FF0000", 800, 25, 30, font)
+        ));
+    }
+
+    pub fn draw_server_status(
+        &mut self,
+        ctx: &mut Context,
+        status: &ServerStatus,
+        font: FontKey,
+    ) {
+        let color = match status {
+            ServerStatus::Running(_) => "00FF00",
+            ServerStatus::Failed(_) => "FF0000",
+            ServerStatus::Starting | ServerStatus::Disabled => "AAAAAA",
+        };
+
+        let text = self.status_text.get(status.clone(), |status| status.to_string());
+        ctx.draw(self.layout.text_at(
+            (START_X + BOARD_WIDTH - 350, 25),
+            self.layout.text(text, color, 380, 18, 22, font)
+        ));
+    }
+
+    //the seed is fixed for the whole game, so the label is only built again for a new one
+    pub fn draw_seed(
+        &mut self,
+        ctx: &mut Context,
+        seed: u64,
+        replaying: bool,
+        font: FontKey,
+    ) {
+        let label = self.seed_text.get((seed, replaying), |&(seed, replaying)| {
+            if replaying {
+                format!("Seed: {} (replay)", seed)
+            } else {
+                format!("Seed: {}", seed)
+            }
+        });
+
+        ctx.draw(self.layout.text_at(
+            (START_X + 15, INFO_Y),
+            self.layout.text(label, "AAAAAA", 800, 18, 22, font)
+        ));
+    }
+
+    //an icon and the seconds left for every running power-up, next to the lives
+    pub fn draw_power_ups(
+        &mut self,
+        ctx: &mut Context,
+        player: &Player,
+        image_handler: &GameImageHandler,
+        font: FontKey,
+    ) {
+        let hud_y = LIVES_Y;
+        let mut x = START_X + MAX_LIVES as u32 * (CELL_SIZE.0 + MARGIN);
+
+        for power_up in PowerUpKind::ALL {
+            let Some(remaining) = player.power_ups.remaining(power_up) else {
+                continue;
+            };
+
+            let seconds = remaining.as_secs_f32().ceil() as u32;
+            let text = self.power_up_texts.entry(power_up).or_default().get(seconds, |seconds| format!("{}s", seconds));
+
+            ctx.draw(self.layout.image((x, hud_y), CELL_SIZE, image_handler.power_up(power_up)));
+            ctx.draw(self.layout.text_at(
+                (x + CELL_SIZE.0 + 2, hud_y + 10),
+                self.layout.text(text, "FFFFFF", 40, 18, 22, font)
+            ));
+            x += CELL_SIZE.0 + 40;
+        }
+    }
+
+    pub fn draw_weapon(
+        &mut self,
+        ctx: &mut Context,
+        weapon: &str,
+        font: FontKey,
+    ) {
+        let label = self.weapon_text.get(weapon.to_string(), |weapon| format!("Weapon: {}", weapon));
+        ctx.draw(self.layout.text_at(
+            (START_X + BOARD_WIDTH - 350, INFO_Y),
+            self.layout.text(label, "AAAAAA", 300, 18, 22, font)
+        ));
+    }
+
+    //the lines only change when a new results screen comes up, not while it counts down
+    pub fn draw_challenge_results(
+        &mut self,
+        ctx: &mut Context,
+        results: &ChallengeResults,
+        font: FontKey,
+    ) {
+        let hits_line = self.hits_text.get((results.hits, results.total), |&(hits, total)| {
+            format!("NUMBER OF HITS {} / {}", hits, total)
+        });
+        let bonus_line = self.bonus_text.get((results.perfect, results.bonus), |&(perfect, bonus)| {
+            if perfect {
+                format!("PERFECT! SPECIAL BONUS {}", bonus)
+            } else {
+                format!("BONUS {}", bonus)
+            }
+        });
+        let lines = ["CHALLENGING STAGE", hits_line, bonus_line];
+
+        for (index, line) in lines.into_iter().enumerate() {
+            let color = if index == 0 { "00FFFF" } else { "FFFFFF" };
+            ctx.draw(self.layout.text_at(
+                (START_X + 200, BOARD_CENTER_Y - 60 + index as u32 * 45),
+                self.layout.text(line, color, 600, 30, 36, font)
+            ));
+        }
+    }
+
+    pub fn draw_stage_banner(
+        &mut self,
+        ctx: &mut Context,
+        level: u32,
+        font: FontKey,
+    ) {
+        let banner = self.banner_text.get(level, |level| format!("STAGE {}", level));
+        ctx.draw(self.layout.text_at(
+            (START_X + 280, BOARD_CENTER_Y - 20),
+            self.layout.text(banner, "00FFFF", 400, 30, 36, font)
         ));
     }
 
     pub fn get_game_canvas_items(
         &self,
         grid: &HashMap<Cords, Box<dyn Ship>>,
+        physics: Option<&PhysicsWorld>,
         player: &Player,
         image_handler: &GameImageHandler,
     ) -> Vec<CanvasItem> {
@@ -53,10 +292,18 @@ impl GameRenderer {
 
         items.append(&mut self.get_grid_items(
             grid,
+            physics,
             player,
             image_handler
         ));
 
+        if let Some(physics) = physics {
+            items.append(&mut self.get_free_projectile_items(
+                physics,
+                image_handler
+            ));
+        }
+
         items.append(&mut self.create_player_canvas_item(
             player,
             image_handler.player
@@ -73,6 +320,7 @@ impl GameRenderer {
     fn get_grid_items(
         &self,
         grid: &HashMap<Cords, Box<dyn Ship>>,
+        physics: Option<&PhysicsWorld>,
         player: &Player,
         image_handler: &GameImageHandler,
     ) -> Vec<CanvasItem> {
@@ -84,7 +332,11 @@ impl GameRenderer {
                 continue;
             }
 
-            let position = self.calculate_screen_position(cords);
+            let (row, column) = match physics.and_then(|physics| physics.position(ship.get_id())) {
+                Some(position) => body_cell(position),
+                None => self.draw_cell(ship.as_ref(), cords),
+            };
+            let position = self.interpolated_screen_position(row, column);
 
             let image_key = self.select_image_for_ship(
                 ship.as_ref(),
@@ -92,11 +344,42 @@ impl GameRenderer {
             );
 
             items.push(self.create_canvas_image_item(position, image_key));
+
+            if self.hit_flashes.contains_key(&ship.get_id()) {
+                items.push(CanvasItem::Shape(
+                    Area((position.0, position.1), None),
+                    Shape::Rectangle(0, self.layout.size(CELL_SIZE)),
+                    "FFFFFF",
+                    140,
+                ));
+            }
+
+            // a captured fighter rides on top of the boss holding it
+            if ship.holds_captive() && row >= 1.0 {
+                let captive_position = self.interpolated_screen_position(row - 1.0, column);
+                items.push(self.create_canvas_image_item(captive_position, image_handler.player));
+            }
         }
 
         items
     }
 
+    //the projectiles flying free of the grid in physics mode
+    fn get_free_projectile_items(
+        &self,
+        physics: &PhysicsWorld,
+        image_handler: &GameImageHandler,
+    ) -> Vec<CanvasItem> {
+        physics
+            .projectiles()
+            .map(|(ship, position)| {
+                let (row, column) = body_cell(position);
+                let position = self.interpolated_screen_position(row, column);
+                self.create_canvas_image_item(position, self.select_image_for_ship(ship, image_handler))
+            })
+            .collect()
+    }
+
     fn create_canvas_image_item(
         &self,
         position: (u32, u32),
@@ -104,7 +387,7 @@ impl GameRenderer {
     ) -> CanvasItem {
         CanvasItem::Image(
             Area((position.0, position.1), None),
-            Shape::Rectangle(0, CELL_SIZE),
+            Shape::Rectangle(0, self.layout.size(CELL_SIZE)),
             image_key,
         )
     }
@@ -115,7 +398,29 @@ impl GameRenderer {
     ) -> (u32, u32) {
         let x = START_X + cords.1 as u32 * (CELL_SIZE.0 + MARGIN);
         let y = START_Y + cords.0 as u32 * (CELL_SIZE.1 + MARGIN);
-        (x, y)
+        self.layout.point((x, y))
+    }
+
+    //where the ship is drawn in (row, column), part way between cells while it glides
+    fn draw_cell(
+        &self,
+        ship: &dyn Ship,
+        cords: &Cords,
+    ) -> (f32, f32) {
+        self.glides
+            .get(&ship.get_id())
+            .filter(|glide| glide.to == *cords)
+            .map_or((cords.0 as f32, cords.1 as f32), |glide| glide.position())
+    }
+
+    fn interpolated_screen_position(
+        &self,
+        row: f32,
+        column: f32,
+    ) -> (u32, u32) {
+        let x = START_X as f32 + column * (CELL_SIZE.0 + MARGIN) as f32;
+        let y = START_Y as f32 + row * (CELL_SIZE.1 + MARGIN) as f32;
+        self.layout.at(x, y)
     }
 
     fn select_image_for_ship(
@@ -123,14 +428,17 @@ impl GameRenderer {
         ship: &dyn Ship,
         image_handler: &GameImageHandler,
     ) -> ImageKey {
-        match ship.display_type() {
-            "fly" => image_handler.fly,
-            "tiki_fly" => image_handler.tiki_fly,
-            "northrop_fly" => image_handler.northrop_fly,
-            "b2_fly" => image_handler.b2_fly,
-            "explosion" => image_handler.explosion,
-            "bullet" => image_handler.bullet_downward,
-            _ => image_handler.fly,
+        match ship.kind() {
+            ShipKind::Enemy(enemy_id) if ship.is_damaged() => image_handler.damaged_enemy(enemy_id),
+            ShipKind::Enemy(enemy_id) => image_handler.enemy(enemy_id),
+            ShipKind::Explosion => image_handler.explosion,
+            ShipKind::TractorBeam => image_handler.tractor_beam,
+            ShipKind::Projectile(projectile) => image_handler.projectile(
+                projectile,
+                ship.faction(),
+                ship.heading().unwrap_or(Heading::Down),
+            ),
+            ShipKind::PowerUp(power_up) => image_handler.power_up(power_up),
         }
     }
 
@@ -141,7 +449,7 @@ impl GameRenderer {
     ) -> Vec<CanvasItem> {
         let mut items = Vec::new();
 
-        if let Some(pos) = player.current_position {
+        for pos in player.cells() {
             let position = self.calculate_screen_position(&pos);
             items.push(self.create_canvas_image_item(position, player_image));
         }
@@ -156,13 +464,16 @@ impl GameRenderer {
     ) -> Vec<CanvasItem> {
         let mut items = Vec::new();
 
-        let lives_y = START_Y + ROWS as u32 * (CELL_SIZE.1 + MARGIN);
-
         for live_idx in 0..player.lives {
             let x = START_X + (live_idx as u32) * (CELL_SIZE.0 + MARGIN);
-            items.push(self.create_canvas_image_item((x, lives_y), player_image));
+            items.push(self.layout.image((x, LIVES_Y), CELL_SIZE, player_image));
         }
 
         items
     }
-}
\ No newline at end of file
+}
+
+//bodies are positioned by their centre in cells, sprites by their top left corner
+fn body_cell(position: Vec2) -> (f32, f32) {
+    (position.y - 0.5, position.x - 0.5)
+}
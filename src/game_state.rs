@@ -0,0 +1,407 @@
+use crate::challenge::{ChallengeResults, ChallengeStage};
+use crate::collision::{player_contact, Contact};
+use crate::enemy::EnemyCatalog;
+use crate::entrance::{Entrance, STAGE_BANNER_DURATION};
+use crate::fly_spawner::FlySpawner;
+use crate::level::LevelLibrary;
+use crate::player::Player;
+use crate::power_up::{new_power_up_ship, PowerUpKind, SCORE_MULTIPLIER};
+use crate::server::DifficultyConfig;
+use crate::settings::{ButtonAction, Settings};
+use crate::ship::{GridEvent, Ship, ShipGrid, ShipKind};
+use crate::ship_ai::AIContext;
+use crate::structs::{stream_seed, Cords, RngStream, ROWS};
+use crate::weapon::{Weapon, WeaponLibrary};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+//everything that can change the game from outside during a step, this is what replays record
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameInput {
+    MoveLeft,
+    MoveRight,
+    Shoot,
+    Reset,
+    Settings(ButtonAction),
+    Difficulty(DifficultyConfig),
+    Weapon(Weapon),
+}
+
+//how ships move and collide, the grid game moves everything a whole cell at a time while physics mode gives every
+//ship a continuous position and hits are decided by overlapping hitboxes, the ai still plays on the grid in both
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    #[default]
+    Grid,
+    Physics,
+}
+
+//what kind of level is being played right now
+pub enum Stage {
+    Entrance(Entrance),
+    Normal,
+    Challenge(Box<ChallengeStage>),
+    Results(ChallengeResults),
+}
+
+//GameState holds the whole simulation and advances only when step is called, it never touches the window or the wall clock.
+pub struct GameState {
+    pub ship_grid: ShipGrid,
+    pub player: Player,
+    pub fly_spawner: FlySpawner,
+    pub settings: Settings,
+    pub stage: Stage,
+    banner: Duration,
+    enemies: Arc<EnemyCatalog>,
+    levels: Arc<LevelLibrary>,
+    weapons: Arc<WeaponLibrary>,
+    mode: GameMode,
+    seed: u64,
+    ai_rng: StdRng,
+    drop_rng: StdRng,
+    elapsed: Duration,
+    steps: u64,
+    events: Vec<GridEvent>,
+}
+
+impl GameState {
+    //the same seed and the same inputs always play out the same game
+    pub fn new(settings: Settings, seed: u64, enemies: Arc<EnemyCatalog>, levels: Arc<LevelLibrary>, weapons: Arc<WeaponLibrary>, mode: GameMode) -> Self {
+        let mut fly_spawner = FlySpawner::new(seed, enemies.clone(), levels.clone());
+        let fly_count = fly_spawner.authored_fly_count(&settings).unwrap_or(settings.value_stats.number_of_flies);
+        let wave = fly_spawner.spawn_flies(fly_count);
+        let mut player = Player::new();
+        player.weapon = weapons.default_weapon().clone();
+
+        let ship_grid = match mode {
+            GameMode::Grid => ShipGrid::new(),
+            GameMode::Physics => ShipGrid::with_physics(),
+        };
+
+        let mut game = GameState {
+            ship_grid,
+            player,
+            fly_spawner,
+            settings,
+            stage: Stage::Normal,
+            banner: Duration::ZERO,
+            enemies,
+            levels,
+            weapons,
+            mode,
+            seed,
+            ai_rng: StdRng::seed_from_u64(stream_seed(seed, RngStream::ShipAI)),
+            drop_rng: StdRng::seed_from_u64(stream_seed(seed, RngStream::Drops)),
+            elapsed: Duration::ZERO,
+            steps: 0,
+            events: Vec::new(),
+        };
+        game.begin_level(wave);
+        game
+    }
+
+    pub fn step(&mut self, dt: Duration, inputs: &[GameInput]) {
+        self.elapsed += dt;
+        self.steps += 1;
+
+        self.player.update(dt);
+        for input in inputs {
+            // the player can't fly or shoot while the stage banner is up
+            let gated = matches!(input, GameInput::MoveLeft | GameInput::MoveRight | GameInput::Shoot);
+            if !gated || self.banner.is_zero() {
+                self.apply_input(input);
+            }
+        }
+        self.handle_player_actions();
+
+        self.ship_grid.score_multiplier = if self.player.power_ups.is_active(PowerUpKind::ScoreMultiplier) { SCORE_MULTIPLIER } else { 1 };
+
+        let ctx = AIContext {
+            settings: &self.settings,
+            dt,
+            player_position: self.player.current_position,
+            level: self.fly_spawner.current_level,
+            hit_points: 0,
+            home: None,
+            speed: self.fly_spawner.speed(),
+            rules: self.fly_spawner.rules(),
+        };
+        self.ship_grid.process_ship_actions(&ctx, &mut self.ai_rng);
+        self.ship_grid.step_physics(&ctx, &self.player.cells());
+        self.handle_grid_events();
+
+        self.check_for_level_completion(dt);
+    }
+
+    pub fn score(&self) -> u32 {
+        self.ship_grid.score
+    }
+
+    pub fn enemies(&self) -> &Arc<EnemyCatalog> {
+        &self.enemies
+    }
+
+    pub fn levels(&self) -> &Arc<LevelLibrary> {
+        &self.levels
+    }
+
+    pub fn weapons(&self) -> &Arc<WeaponLibrary> {
+        &self.weapons
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    //total simulated time since the game was created
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    //what happened on the grid during the last step, for feedback that isn't part of the simulation
+    pub fn events(&self) -> &[GridEvent] {
+        &self.events
+    }
+
+    //number of steps taken so far
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn reset(&mut self) {
+        self.fly_spawner = FlySpawner::new(self.seed, self.enemies.clone(), self.levels.clone());
+        self.ai_rng = StdRng::seed_from_u64(stream_seed(self.seed, RngStream::ShipAI));
+        self.drop_rng = StdRng::seed_from_u64(stream_seed(self.seed, RngStream::Drops));
+        self.spawn_initial_flies();
+    }
+
+    fn apply_input(&mut self, input: &GameInput) {
+        match input {
+            GameInput::MoveLeft => self.player.move_left(),
+            GameInput::MoveRight => self.player.move_right(),
+            GameInput::Shoot => {
+                let on_screen = self.ship_grid.player_bullet_count();
+                self.player.shoot(&mut self.ship_grid.grid, on_screen, &self.weapons);
+            },
+            GameInput::Reset => self.reset(),
+            GameInput::Settings(action) => {
+                match self.settings.apply_action(action.clone()) {
+                    Some(ButtonAction::Reset) => self.reset(),
+                    Some(ButtonAction::NextWeapon) => {
+                        self.player.weapon = self.weapons.next_after(&self.player.weapon.name).clone();
+                    }
+                    _ => {}
+                }
+            },
+            GameInput::Difficulty(config) => self.settings.value_stats.apply_difficulty(config),
+            GameInput::Weapon(weapon) => self.player.weapon = weapon.clone(),
+        }
+    }
+
+    fn check_for_level_completion(&mut self, dt: Duration) {
+        // nothing starts coming in until the banner is gone
+        if !self.banner.is_zero() {
+            self.banner = self.banner.saturating_sub(dt);
+            return;
+        }
+
+        let fly_count = self.ship_grid.grid.values().filter(|ship| ship.is_enemy()).count();
+
+        let explosion_count = self.ship_grid.grid.values().filter(|ship| ship.is_effect()).count();
+
+        // a drop still falling gets the chance to be picked up before the board is replaced
+        let drop_count = self.ship_grid.grid.values().filter(|ship| ship.kind().is_pickup()).count();
+
+        let cleared = fly_count == 0 && explosion_count == 0 && drop_count == 0;
+
+        match &mut self.stage {
+            Stage::Entrance(entrance) => {
+                entrance.update(dt, &mut self.ship_grid);
+
+                if entrance.is_done() {
+                    self.stage = Stage::Normal;
+                }
+            }
+            Stage::Normal => {
+                if cleared {
+                    self.start_next_level();
+                }
+            }
+            Stage::Challenge(challenge) => {
+                challenge.update(dt, &mut self.ship_grid, &self.enemies);
+
+                if cleared && challenge.is_spawning_done() {
+                    let results = challenge.results();
+                    self.ship_grid.score += results.bonus;
+                    self.stage = Stage::Results(results);
+                }
+            }
+            Stage::Results(results) => {
+                if results.tick(dt) {
+                    self.stage = Stage::Normal;
+                    self.start_next_level();
+                }
+            }
+        }
+    }
+
+    fn start_next_level(&mut self) {
+        if self.fly_spawner.is_challenge_level(self.fly_spawner.current_level + 1) {
+            let seed = self.fly_spawner.advance_level();
+            self.ship_grid.set_wave(HashMap::new());
+            self.stage = Stage::Challenge(Box::new(ChallengeStage::new(seed)));
+            self.banner = STAGE_BANNER_DURATION;
+        } else {
+            let wave = self.fly_spawner.spawn_next_level(&self.settings);
+            self.begin_level(wave);
+        }
+    }
+
+    //shows the stage banner and lets the new wave fly in once it is gone
+    fn begin_level(&mut self, wave: HashMap<Cords, Box<dyn Ship>>) {
+        self.ship_grid.set_incoming_wave(&wave);
+        self.stage = Stage::Entrance(Entrance::new(wave, &self.enemies, self.fly_spawner.entry_script()));
+        self.banner = STAGE_BANNER_DURATION;
+    }
+
+    //the level the "STAGE N" banner is announcing, while it is up
+    pub fn stage_banner(&self) -> Option<u32> {
+        (!self.banner.is_zero()).then_some(self.fly_spawner.current_level)
+    }
+
+    //the results screen after a challenging stage, while it is up
+    pub fn challenge_results(&self) -> Option<&ChallengeResults> {
+        match &self.stage {
+            Stage::Results(results) => Some(results),
+            Stage::Entrance(_) | Stage::Normal | Stage::Challenge(_) => None,
+        }
+    }
+
+    fn spawn_initial_flies(&mut self) {
+        self.fly_spawner.reset_level();
+        self.ship_grid.clear();
+        let wave = self.fly_spawner.spawn_flies(self.fly_spawner.get_current_fly_count(&self.settings));
+        self.begin_level(wave);
+    }
+
+    fn handle_player_actions(&mut self) -> bool {
+        // in physics mode the hitboxes decide what touches the fighter, see handle_grid_events
+        if self.mode == GameMode::Physics {
+            return false;
+        }
+
+        let mut player_died = false;
+
+        for pos in self.player.cells() {
+            player_died = self.fighter_contact(pos);
+
+            if player_died {
+                self.spawn_initial_flies();
+                break;
+            }
+        }
+
+        player_died
+    }
+
+    //the fighter touching the ship in `pos`, returns true when it cost the last life
+    fn fighter_contact(&mut self, pos: Cords) -> bool {
+        let Some(ship) = self.ship_grid.grid.get(&pos) else {
+            return false;
+        };
+        let (contact, kind, owner) = (player_contact(ship.as_ref()), ship.kind(), ship.owner());
+
+        match contact {
+            Contact::Collect => {
+                self.ship_grid.grid.remove(&pos);
+                if let ShipKind::PowerUp(power_up) = kind {
+                    self.player.collect(power_up);
+                }
+                false
+            }
+            Contact::Capture => owner.is_some_and(|owner| self.capture_player(owner)),
+            Contact::Crash => {
+                let invincible = self.is_invincible();
+                self.player.handle_collision(
+                    &mut self.ship_grid.grid,
+                    pos,
+                    &mut self.ship_grid.score,
+                    invincible)
+            }
+            Contact::Ignore => false,
+        }
+    }
+
+    //the boss that fired the beam takes the fighter with it
+    fn capture_player(&mut self, owner: Uuid) -> bool {
+        if self.is_invincible() {
+            return false;
+        }
+
+        self.ship_grid.clear_tractor_beam(owner);
+        if let Some(boss) = self.ship_grid.find_by_id(owner) {
+            boss.capture_fighter();
+        }
+        self.player.capture()
+    }
+
+    //the debug toggle or a running shield
+    fn is_invincible(&self) -> bool {
+        self.settings.value_stats.invincible || self.player.power_ups.is_active(PowerUpKind::Shield)
+    }
+
+    fn handle_grid_events(&mut self) {
+        let events = std::mem::take(&mut self.ship_grid.events);
+        let mut player_died = false;
+        for event in &events {
+            match event {
+                GridEvent::FighterRescued => self.player.rescue_fighter(),
+                // a fighter cell lost earlier in the step is gone, whatever reached it there missed
+                GridEvent::FighterStruck(fighter) if !player_died && self.player.cells().contains(fighter) => {
+                    let invincible = self.is_invincible();
+                    player_died = self.player.struck(*fighter, invincible);
+                }
+                GridEvent::FighterTouched { ship, fighter } if !player_died && self.player.cells().contains(fighter) => {
+                    player_died = self.fighter_contact(*ship);
+                }
+                GridEvent::FighterStruck(_) | GridEvent::FighterTouched { .. } => {}
+                GridEvent::ShipHit(_) | GridEvent::ShipMoved { .. } => {}
+                GridEvent::EnemyDestroyed(cords) => {
+                    if let Stage::Challenge(challenge) = &mut self.stage {
+                        challenge.record_hit();
+                    }
+                    self.roll_drop(*cords);
+                }
+            }
+        }
+        self.events = events;
+
+        if player_died {
+            self.spawn_initial_flies();
+        }
+    }
+
+    //destroyed enemies drop a power-up drop_percentage of the time, it falls from the cell below the explosion
+    fn roll_drop(&mut self, cords: Cords) {
+        if self.drop_rng.random_range(0.0..100.0) >= self.settings.value_stats.drop_percentage {
+            return;
+        }
+
+        let power_up = PowerUpKind::random(&mut self.drop_rng);
+        let drop_cords = Cords(cords.0 + 1, cords.1);
+        if drop_cords.0 < ROWS && !self.ship_grid.grid.contains_key(&drop_cords) {
+            self.ship_grid.grid.insert(drop_cords, new_power_up_ship(power_up));
+        }
+    }
+}
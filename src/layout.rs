@@ -0,0 +1,139 @@
+use rust_on_rails::prelude::*;
+use crate::structs::{CELL_SIZE, COLUMNS, MARGIN, ROWS, START_X, START_Y};
+
+//the board, the hud and the settings panel are laid out in design units on a canvas this wide, it is scaled to fit the
+//window keeping its shape and centred with bars on the sides it doesn't fill
+pub const DESIGN_WIDTH: u32 = 850;
+pub const DESIGN_HEIGHT: u32 = PANEL_Y + PANEL_HEIGHT + 20;
+
+pub const BOARD_WIDTH: u32 = COLUMNS as u32 * (CELL_SIZE.0 + MARGIN) - MARGIN;
+//the row under the board with the lives and running power-ups, and the one under it with the seed and weapon
+pub const LIVES_Y: u32 = START_Y + ROWS as u32 * (CELL_SIZE.1 + MARGIN);
+pub const INFO_Y: u32 = LIVES_Y + CELL_SIZE.1 + MARGIN;
+pub const BOARD_CENTER_Y: u32 = START_Y + (ROWS as u32 / 2) * (CELL_SIZE.1 + MARGIN);
+
+//the settings panel sits under the info row across the width of the board
+pub const PANEL_X: u32 = START_X + 4;
+pub const PANEL_Y: u32 = INFO_Y + 60;
+pub const PANEL_WIDTH: u32 = BOARD_WIDTH + 10;
+pub const PANEL_HEIGHT: u32 = 220;
+pub const PANEL_PADDING: u32 = 21;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    window: (u32, u32),
+    scale: f32,
+    origin: (f32, f32),
+}
+
+impl Layout {
+    pub fn new(window: (u32, u32)) -> Self {
+        let window = (window.0.max(1), window.1.max(1));
+        let scale = (window.0 as f32 / DESIGN_WIDTH as f32).min(window.1 as f32 / DESIGN_HEIGHT as f32);
+        let origin = (
+            (window.0 as f32 - DESIGN_WIDTH as f32 * scale) / 2.0,
+            (window.1 as f32 - DESIGN_HEIGHT as f32 * scale) / 2.0,
+        );
+
+        Layout { window, scale, origin }
+    }
+
+    pub fn window(&self) -> (u32, u32) {
+        self.window
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    //a design position in window pixels, fractional positions are for ships part way between cells
+    pub fn at(&self, x: f32, y: f32) -> (u32, u32) {
+        (
+            (self.origin.0 + x * self.scale).round().max(0.0) as u32,
+            (self.origin.1 + y * self.scale).round().max(0.0) as u32,
+        )
+    }
+
+    pub fn point(&self, position: (u32, u32)) -> (u32, u32) {
+        self.at(position.0 as f32, position.1 as f32)
+    }
+
+    pub fn length(&self, length: u32) -> u32 {
+        ((length as f32 * self.scale).round() as u32).max(1)
+    }
+
+    pub fn size(&self, size: (u32, u32)) -> (u32, u32) {
+        (self.length(size.0), self.length(size.1))
+    }
+
+    //a click in window pixels back in design units, None when it landed in the bars
+    pub fn to_design(&self, position: (u32, u32)) -> Option<(u32, u32)> {
+        let x = (position.0 as f32 - self.origin.0) / self.scale;
+        let y = (position.1 as f32 - self.origin.1) / self.scale;
+        let inside = x >= 0.0 && y >= 0.0 && x < DESIGN_WIDTH as f32 && y < DESIGN_HEIGHT as f32;
+        inside.then_some((x.round() as u32, y.round() as u32))
+    }
+
+    //the parts of the window outside the design canvas as (position, size) in window pixels
+    pub fn bars(&self) -> Vec<((u32, u32), (u32, u32))> {
+        let (x, y) = (self.origin.0.round() as u32, self.origin.1.round() as u32);
+        let mut bars = Vec::new();
+        if x > 0 {
+            bars.push(((0, 0), (x, self.window.1)));
+            bars.push(((self.window.0 - x, 0), (x, self.window.1)));
+        }
+        if y > 0 {
+            bars.push(((0, 0), (self.window.0, y)));
+            bars.push(((0, self.window.1 - y), (self.window.0, y)));
+        }
+        bars
+    }
+
+    pub fn image(&self, position: (u32, u32), size: (u32, u32), image: ImageKey) -> CanvasItem {
+        CanvasItem::Image(Area(self.point(position), None), Shape::Rectangle(0, self.size(size)), image)
+    }
+
+    pub fn rectangle(&self, position: (u32, u32), size: (u32, u32), color: &'static str, alpha: u8) -> CanvasItem {
+        CanvasItem::Shape(Area(self.point(position), None), Shape::Rectangle(0, self.size(size)), color, alpha)
+    }
+
+    pub fn rounded_rectangle(&self, position: (u32, u32), size: (u32, u32), radius: u32, color: &'static str, alpha: u8) -> CanvasItem {
+        CanvasItem::Shape(
+            Area(self.point(position), None),
+            Shape::RoundedRectangle(0, self.size(size), self.length(radius)),
+            color,
+            alpha,
+        )
+    }
+
+    //text at design size, `width` is where it wraps
+    pub fn text(
+        &self,
+        content: &'static str,
+        color: &'static str,
+        width: u32,
+        size: u32,
+        line_height: u32,
+        font: FontKey,
+    ) -> Text {
+        Text::new(
+            content,
+            color,
+            255,
+            Some(self.length(width)),
+            self.length(size),
+            self.length(line_height),
+            font,
+        )
+    }
+
+    pub fn text_at(&self, position: (u32, u32), text: Text) -> CanvasItem {
+        CanvasItem::Text(Area(self.point(position), None), text)
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::new((DESIGN_WIDTH, DESIGN_HEIGHT))
+    }
+}
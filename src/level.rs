@@ -0,0 +1,166 @@
+use crate::enemy::EnemyCatalog;
+use crate::formation::FormationPattern;
+use crate::structs::{fingerprint, Cords, COLUMNS, EMPTY_FINGERPRINT, ROWS};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+
+const BUILTIN_LEVELS: &str = include_str!("../assets/levels.json");
+
+//the bottom two rows belong to the player
+pub const PLAYER_ROWS: usize = 2;
+
+//either one of the named patterns or an explicit list of [row, col] cells
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FormationLayout {
+    Pattern(String),
+    Cells { cells: Vec<Cords> },
+}
+
+//switches that change how a level plays
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelRules {
+    pub challenge: bool,
+    pub no_shooting: bool,
+    pub no_dives: bool,
+}
+
+//one authored level, anything left out is filled in the same way as procedural levels
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LevelDefinition {
+    #[serde(default)]
+    pub formation: Option<FormationLayout>,
+    #[serde(default)]
+    pub count: Option<u32>,
+    //enemy name to spawn weight, empty uses every enemy's own spawn_weight
+    #[serde(default)]
+    pub enemies: BTreeMap<String, u32>,
+    //ai script the enemies fly when the level starts before settling into their slots
+    #[serde(default)]
+    pub entry: Option<String>,
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+    #[serde(default)]
+    pub rules: LevelRules,
+}
+
+fn default_speed() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LevelFile {
+    levels: Vec<LevelDefinition>,
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(String, std::io::Error),
+    Parse(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Io(path, err) => write!(f, "{}: {}", path, err),
+            LevelError::Parse(err) => write!(f, "line {}: {}", err.line(), err),
+            LevelError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+//the authored levels in order, level 1 is the first entry
+#[derive(Debug, Clone, Default)]
+pub struct LevelLibrary {
+    levels: Vec<LevelDefinition>,
+    fingerprint: u64,
+}
+
+impl LevelLibrary {
+    //the levels shipped in assets/levels.json, they name builtin enemies so a custom enemies file may not fit them
+    pub fn builtin(enemies: &EnemyCatalog) -> Self {
+        Self::from_json(BUILTIN_LEVELS, enemies).unwrap_or_else(|err| {
+            eprintln!("Built in levels don't fit the loaded enemies, every level will be procedural: {}", err);
+            Self::default()
+        })
+    }
+
+    pub fn load(path: &str, enemies: &EnemyCatalog) -> Result<Self, LevelError> {
+        let contents = fs::read_to_string(path).map_err(|err| LevelError::Io(path.to_string(), err))?;
+        Self::from_json(&contents, enemies)
+    }
+
+    pub fn from_json(contents: &str, enemies: &EnemyCatalog) -> Result<Self, LevelError> {
+        let file: LevelFile = serde_json::from_str(contents).map_err(LevelError::Parse)?;
+
+        for (index, level) in file.levels.iter().enumerate() {
+            Self::validate(level, enemies).map_err(|message| LevelError::Invalid(format!("level {}: {}", index + 1, message)))?;
+        }
+
+        Ok(LevelLibrary { levels: file.levels, fingerprint: fingerprint(EMPTY_FINGERPRINT, contents.as_bytes()) })
+    }
+
+    //changes whenever the levels file does, 0 when every level is procedural
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    fn validate(level: &LevelDefinition, enemies: &EnemyCatalog) -> Result<(), String> {
+        match &level.formation {
+            Some(FormationLayout::Pattern(name)) if FormationPattern::from_name(name).is_none() => {
+                let names: Vec<&str> = FormationPattern::ALL.iter().map(|pattern| pattern.name()).collect();
+                return Err(format!("unknown formation `{}`, expected one of {}", name, names.join(", ")));
+            }
+            Some(FormationLayout::Cells { cells }) => {
+                let mut seen = HashSet::new();
+                for cell in cells {
+                    if cell.0 >= ROWS - PLAYER_ROWS || cell.1 >= COLUMNS {
+                        return Err(format!("cell [{}, {}] is outside the enemy area", cell.0, cell.1));
+                    }
+                    if !seen.insert(cell) {
+                        return Err(format!("cell [{}, {}] is listed twice", cell.0, cell.1));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        for name in level.enemies.keys() {
+            if enemies.find(name).is_none() {
+                return Err(format!("unknown enemy `{}`", name));
+            }
+        }
+        if !level.enemies.is_empty() && level.enemies.values().all(|&weight| weight == 0) {
+            return Err(String::from("at least one enemy needs a weight above 0"));
+        }
+
+        if let Some(entry) = &level.entry {
+            if enemies.named_script(entry).is_none() {
+                return Err(format!("unknown entry script `{}`", entry));
+            }
+        }
+
+        if level.speed <= 0.0 || !level.speed.is_finite() {
+            return Err(String::from("speed has to be above 0"));
+        }
+
+        Ok(())
+    }
+
+    //None once the game has run past the last authored level
+    pub fn get(&self, level: u32) -> Option<&LevelDefinition> {
+        self.levels.get((level as usize).checked_sub(1)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+}
@@ -1,47 +1,80 @@
 use rand::Rng;
 use rust_on_rails::prelude::*;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::fly_spawner::FlySpawner;
+use crate::ai_script::AIScriptLibrary;
+use crate::enemy::EnemyCatalog;
+use crate::level::LevelLibrary;
 use crate::game_image_handler::GameImageHandler;
 use crate::game_renderer::GameRenderer;
+use crate::game_state::{GameInput, GameState};
+use crate::layout::Layout;
 use crate::message_processor::MessageProcessor;
-use crate::player::Player;
-use crate::settings::{Settings, ButtonAction};
-use crate::ship::ShipGrid;
+use crate::replay::{content_fingerprint, Replay, ReplayPlayer, ReplayRecorder};
+use crate::settings::{GameConfig, Settings};
+use crate::settings_panel::SettingsPanel;
+use crate::ship::GridEvent;
 use crate::star_background::StarBackground;
+use crate::weapon::WeaponLibrary;
 
 use prelude::App;
 use server::run_server;
-use server::PressurePadData;
-use tokio::sync::mpsc;
+use server::{DifficultyConfig, DifficultyState, PressurePadData, ServerConfig, ServerStatus, WeaponState};
+use tokio::sync::{mpsc, watch, RwLock};
 use tokio::sync::Mutex as TokioMutex;
 
-mod structs;
-mod ship;
-mod player;
-mod settings;
-mod server;
+pub mod structs;
+pub mod ship;
+pub mod player;
+pub mod settings;
+mod settings_panel;
+pub mod server;
 mod game_image_handler;
 mod message_processor;
 mod game_renderer;
-mod fly_spawner;
+pub mod fly_spawner;
 mod star_background;
-mod ship_ai;
-
+pub mod ship_ai;
+pub mod game_state;
+pub mod replay;
+pub mod enemy;
+pub mod ai_script;
+pub mod formation;
+pub mod challenge;
+pub mod level;
+pub mod entrance;
+pub mod power_up;
+pub mod weapon;
+pub mod collision;
+pub mod physics;
+pub mod layout;
+
+//the window size used until the platform reports one
 const DEFAULT_WIDTH: u32 = 850;
 const DEFAULT_HEIGHT: u32 = 1300;
+const STEP: Duration = Duration::from_millis(16);
+const MAX_STEPS_PER_FRAME: u32 = 8;
 
 pub struct MyApp {
-    window_size: (u32, u32),
+    layout: Layout,
     images: GameImageHandler,
     rx_arc: Arc<TokioMutex<mpsc::Receiver<PressurePadData>>>,
-    ship_grid: ShipGrid,
-    player: Player,
-    settings: Settings,
+    server_status: watch::Receiver<ServerStatus>,
+    difficulty_state: Arc<RwLock<DifficultyState>>,
+    applied_difficulty: DifficultyConfig,
+    weapon_state: Arc<RwLock<WeaponState>>,
+    game: GameState,
+    pending_inputs: Vec<GameInput>,
+    last_frame: Option<Instant>,
+    step_accumulator: Duration,
+    step: Duration,
+    auto_fire: bool,
+    recorder: Option<ReplayRecorder>,
+    replay_player: Option<ReplayPlayer>,
     message_processor: MessageProcessor,
     game_renderer: GameRenderer,
-    fly_spawner: FlySpawner,
+    settings_panel: SettingsPanel,
     star_background: Option<StarBackground>,
     font: FontKey,
 }
@@ -51,36 +84,134 @@ impl App for MyApp {
         let (tx, rx) = mpsc::channel::<PressurePadData>(100);
         let rx_arc = Arc::new(TokioMutex::new(rx));
 
-        let window_size = (DEFAULT_WIDTH, DEFAULT_HEIGHT);
+        let window_size = match ctx.size {
+            (0, _) | (_, 0) => (DEFAULT_WIDTH, DEFAULT_HEIGHT),
+            size => size,
+        };
         let font = ctx.add_font(include_bytes!("../assets/fonts/outfit_bold.ttf").to_vec());
 
+        let server_config = ServerConfig::load();
+        let (status_tx, server_status) = watch::channel(ServerStatus::Starting);
+        let difficulty_state = Arc::new(RwLock::new(DifficultyState::default()));
+        let applied_difficulty = difficulty_state.read().await.config.clone();
+        let server_difficulty_state = difficulty_state.clone();
+        let weapon_state = Arc::new(RwLock::new(WeaponState::default()));
+        let server_weapon_state = weapon_state.clone();
         tokio::spawn(async move {
-            run_server(tx).await;
+            run_server(tx, server_config, server_difficulty_state, server_weapon_state, status_tx).await;
+        });
+
+        let game_config = GameConfig::load();
+        let ai_scripts = match &game_config.ai_scripts_file {
+            Some(path) => AIScriptLibrary::load(path).unwrap_or_else(|err| {
+                eprintln!("Failed to load ai scripts, using the built in ones: {}", err);
+                AIScriptLibrary::builtin()
+            }),
+            None => AIScriptLibrary::builtin(),
+        };
+
+        let enemies = Arc::new(match &game_config.enemies_file {
+            Some(path) => EnemyCatalog::load(path, &ai_scripts).unwrap_or_else(|err| {
+                eprintln!("Failed to load enemies, using the built in ones: {}", err);
+                EnemyCatalog::builtin(&ai_scripts)
+            }),
+            None => EnemyCatalog::builtin(&ai_scripts),
+        });
+
+        let levels = Arc::new(match &game_config.levels_file {
+            Some(path) => LevelLibrary::load(path, &enemies).unwrap_or_else(|err| {
+                eprintln!("Failed to load levels, using the built in ones: {}", err);
+                LevelLibrary::builtin(&enemies)
+            }),
+            None => LevelLibrary::builtin(&enemies),
+        });
+
+        let weapons = Arc::new(match &game_config.weapons_file {
+            Some(path) => WeaponLibrary::load(path).unwrap_or_else(|err| {
+                eprintln!("Failed to load weapons, using the built in ones: {}", err);
+                WeaponLibrary::builtin()
+            }),
+            None => WeaponLibrary::builtin(),
+        });
+        weapon_state.write().await.available = weapons.all().to_vec();
+
+        let replay_player = game_config.replay_file.as_ref().and_then(|path| {
+            // a replay only plays back against the content files it was recorded with
+            let replay = Replay::load(path).and_then(|replay| replay.check_content(&enemies, &levels, &weapons).map(|_| replay));
+            match replay {
+                Ok(replay) => Some(replay),
+                Err(err) => {
+                    eprintln!("Failed to load replay {}: {}", path, err);
+                    None
+                }
+            }
         });
 
-        let settings = Settings::new();
+        let step = replay_player.as_ref().map_or(STEP, |replay| replay.step_duration());
+        let seed = match &replay_player {
+            Some(replay) => replay.header.seed,
+            None => game_config.seed.unwrap_or_else(|| rand::rng().random()),
+        };
+        let mode = replay_player.as_ref().map_or(game_config.mode, |replay| replay.header.mode);
+        println!("Game seed: {}", seed);
+
+        let recorder = if replay_player.is_none() && game_config.record_replays {
+            match ReplayRecorder::create(&game_config.replay_dir, seed, step, mode, content_fingerprint(&enemies, &levels, &weapons)) {
+                Ok(recorder) => {
+                    println!("Recording replay to {}", recorder.path().display());
+                    Some(recorder)
+                }
+                Err(err) => {
+                    eprintln!("Failed to start replay recording: {}", err);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let replay_player = replay_player.map(ReplayPlayer::new);
+
+        // the starting difficulty and weapon go in as inputs so recordings replay with them
+        let mut pending_inputs = vec![GameInput::Difficulty(applied_difficulty.clone())];
+        if let Some(name) = &game_config.weapon {
+            match weapons.get(name) {
+                Some(weapon) => pending_inputs.push(GameInput::Weapon(weapon.clone())),
+                None => eprintln!("Unknown weapon {}, starting with {}", name, weapons.default_weapon().name),
+            }
+        }
 
         let pressure_threshold = 600;
-        let mut ship_grid = ShipGrid::new();
-        let mut fly_spawner = FlySpawner::new();
-        let message_processor = MessageProcessor::new(pressure_threshold);
-        let game_renderer = GameRenderer::new();
-        let star_background = Some(StarBackground::new(window_size));
-        let player = Player::new();
-        ship_grid.grid = fly_spawner.spawn_flies(settings.value_stats.number_of_flies.clone());
+        let game = GameState::new(Settings::new(), seed, enemies.clone(), levels, weapons, mode);
+        let mut message_processor = MessageProcessor::new(pressure_threshold);
+        if let Some(interval) = game_config.pad_repeat_ms {
+            message_processor = message_processor.with_auto_repeat(Duration::from_millis(interval));
+        }
+        let layout = Layout::new(window_size);
+        let mut game_renderer = GameRenderer::new();
+        game_renderer.set_layout(layout);
+        let star_background = Some(StarBackground::new(window_size, seed));
 
-        let images = GameImageHandler::new(ctx);
+        let images = GameImageHandler::new(ctx, &enemies);
 
         MyApp {
-            window_size,
+            layout,
             images,
             rx_arc,
-            ship_grid,
-            player,
-            settings,
+            server_status,
+            difficulty_state,
+            applied_difficulty,
+            weapon_state,
+            game,
+            pending_inputs,
+            last_frame: None,
+            step_accumulator: Duration::ZERO,
+            step,
+            auto_fire: game_config.auto_fire,
+            recorder,
+            replay_player,
             message_processor,
             game_renderer,
-            fly_spawner,
+            settings_panel: SettingsPanel::new(),
             star_background,
             font,
         }
@@ -88,38 +219,50 @@ impl App for MyApp {
 
     async fn draw(&mut self, ctx: &mut Context) {
         self.process_game_state();
+        self.fit_window(ctx.size);
 
         ctx.clear("000000");
         if let Some(star_background) = &self.star_background {
             star_background.draw(ctx);
         }
 
+        // the stars fill the whole window, the bars only dim them where the game doesn't reach
+        for (position, size) in self.layout.bars() {
+            ctx.draw(CanvasItem::Shape(Area(position, None), Shape::Rectangle(0, size), "000000", 180));
+        }
+
         self.game_renderer.draw(
             ctx,
-            &self.ship_grid.grid,
-            &self.player,
+            &self.game.ship_grid.grid,
+            self.game.ship_grid.physics.as_ref(),
+            &self.game.player,
             &self.images,
-            self.get_score(),
+            self.game.score(),
             self.font,
         );
 
-        ctx.draw(CanvasItem::Shape(
-            Area((9, 760), None),
-            Shape::RoundedRectangle(0, (805, 220), 10),
-            "0D1F2D",
-            255
-        ));
+        self.game_renderer.draw_server_status(ctx, &self.server_status.borrow(), self.font);
+        self.game_renderer.draw_seed(ctx, self.game.seed(), self.replay_player.is_some(), self.font);
+        self.game_renderer.draw_power_ups(ctx, &self.game.player, &self.images, self.font);
+        self.game_renderer.draw_weapon(ctx, &self.game.player.active_weapon(self.game.weapons()).name, self.font);
+
+        if let Some(results) = self.game.challenge_results() {
+            self.game_renderer.draw_challenge_results(ctx, results, self.font);
+        }
 
-        self.settings.draw(ctx, self.font);
+        if let Some(level) = self.game.stage_banner() {
+            self.game_renderer.draw_stage_banner(ctx, level, self.font);
+        }
+
+        self.settings_panel.draw(ctx, &self.layout, &self.game.settings.value_stats, self.font);
     }
 
     async fn on_click(&mut self, ctx: &mut Context) {
-        let position = ctx.position;
-        if let Some(action) = self.settings.handle_click(position.0, position.1) {
-            if matches!(action, ButtonAction::Reset) {
-                self.spawn_initial_flies();
-                self.fly_spawner = FlySpawner::new();
-            }
+        let Some(position) = self.layout.to_design(ctx.position) else {
+            return;
+        };
+        if let Some(action) = self.settings_panel.find_clicked_button(position.0, position.1) {
+            self.pending_inputs.push(GameInput::Settings(action));
         }
     }
 
@@ -129,63 +272,100 @@ impl App for MyApp {
 }
 
 impl MyApp {
-    fn get_score(&self) -> u32 {
-        self.ship_grid.score
-    }
+    //rescales everything to the window when it changes size, the stars are scattered again over the new area
+    fn fit_window(&mut self, window_size: (u32, u32)) {
+        if window_size.0 == 0 || window_size.1 == 0 || window_size == self.layout.window() {
+            return;
+        }
 
+        self.layout = Layout::new(window_size);
+        self.game_renderer.set_layout(self.layout);
+        self.star_background = Some(StarBackground::new(window_size, self.game.seed()));
+    }
 
+    //steps the game at a fixed rate so a replay only needs the step number of each input
     fn process_game_state(&mut self) {
-        self.player.update();
-        self.player.shoot(&mut self.ship_grid.grid);
-        self.handle_player_actions();
+        let now = Instant::now();
+        let dt = self.last_frame.map_or(Duration::ZERO, |last| now.duration_since(last));
+        self.last_frame = Some(now);
+        self.step_accumulator = (self.step_accumulator + dt).min(self.step * MAX_STEPS_PER_FRAME);
+
+        self.sync_difficulty();
+        self.sync_weapon();
+        self.handle_pad_input();
 
         if let Some(star_background) = &mut self.star_background {
             star_background.on_tick();
         }
 
-        self.ship_grid.process_ship_actions(&self.settings);
+        while self.step_accumulator >= self.step {
+            self.step_accumulator -= self.step;
+
+            let inputs = match &mut self.replay_player {
+                Some(replay_player) => {
+                    self.pending_inputs.clear();
+                    replay_player.inputs_for(self.game.steps())
+                }
+                None => {
+                    let mut inputs = std::mem::take(&mut self.pending_inputs);
+                    // recorded like a pad press so replays don't need the setting
+                    if self.auto_fire {
+                        inputs.push(GameInput::Shoot);
+                    }
+                    inputs
+                }
+            };
+
+            if let Some(recorder) = &mut self.recorder {
+                if let Err(err) = recorder.record(self.game.steps(), &inputs) {
+                    eprintln!("Stopped recording replay: {}", err);
+                    self.recorder = None;
+                }
+            }
 
-        self.check_for_level_completion();
-    }
+            self.game.step(self.step, &inputs);
 
-    fn check_for_level_completion(&mut self) {
-        let fly_count = self.ship_grid.grid.iter().filter(|(_, ship)| {
-            let ship_type = ship.display_type();
-            ship_type == "fly" || ship_type == "tiki_fly" ||
-                ship_type == "northrop_fly" || ship_type == "b2_fly"
-        }).count();
+            for event in self.game.events() {
+                match event {
+                    GridEvent::ShipHit(ship_id) => self.game_renderer.flash(*ship_id),
+                    GridEvent::ShipMoved { id, from, to, interval, .. } => self.game_renderer.glide(*id, *from, *to, *interval),
+                    _ => {}
+                }
+            }
+        }
 
-        let explosion_count = self.ship_grid.grid.iter()
-            .filter(|(_, ship)| ship.display_type() == "explosion")
-            .count();
+        self.game_renderer.tick(dt);
+    }
 
-        if fly_count == 0 && explosion_count == 0 {
-            self.ship_grid.grid = self.fly_spawner.spawn_next_level();
+    //picks up difficulty changes from the server and reports back what is actually in use
+    fn sync_difficulty(&mut self) {
+        if let Ok(mut state) = self.difficulty_state.try_write() {
+            if state.config != self.applied_difficulty {
+                self.pending_inputs.push(GameInput::Difficulty(state.config.clone()));
+                self.applied_difficulty = state.config.clone();
+            }
+            state.applied = self.game.settings.value_stats.applied_difficulty();
         }
     }
 
-    fn spawn_initial_flies(&mut self) {
-        self.fly_spawner.reset_level();
-        self.ship_grid.clear();
-        self.ship_grid.grid = self.fly_spawner.spawn_flies(self.fly_spawner.get_current_fly_count());
+    //picks up a weapon asked for over the server and reports back what the fighter is shooting with
+    fn sync_weapon(&mut self) {
+        if let Ok(mut state) = self.weapon_state.try_write() {
+            if let Some(weapon) = state.requested.take() {
+                self.pending_inputs.push(GameInput::Weapon(weapon));
+            }
+            state.current = self.game.player.weapon.clone();
+            state.active = self.game.player.active_weapon(self.game.weapons()).clone();
+        }
     }
 
-    fn handle_player_actions(&mut self) -> bool {
-        let mut player_died = false;
-
-        if let Some(pos) = self.player.current_position {
-            player_died = self.player.handle_collision(
-                &mut self.ship_grid.grid,
-                pos,
-                &mut self.ship_grid.score,
-                self.settings.value_stats.invincible);
-
-            if player_died {
-                self.spawn_initial_flies();
+    fn handle_pad_input(&mut self) {
+        if let Ok(mut rx) = self.rx_arc.try_lock() {
+            let inputs = self.message_processor.drain_pending(&mut rx);
+            if self.replay_player.is_none() {
+                self.pending_inputs.extend(inputs);
             }
         }
-
-        player_died
     }
 }
 
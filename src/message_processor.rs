@@ -1,24 +1,52 @@
-use crate::player::Player;
+use crate::game_state::GameInput;
 use crate::server::{PadType, PressurePadData};
-use crate::ship::Ship;
-use crate::structs::Cords;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::Receiver;
-use tokio::sync::Mutex;
 
-//process received messages from server and executes like player shoot if message was shoot or move left, move right.
+const DEFAULT_RELEASE_TIMEOUT_MS: u64 = 250;
+
+//tracks whether a pad is currently held so a held pad only counts as one press
+struct PadState {
+    held: bool,
+    last_seen: Instant,
+    last_fired: Instant,
+}
+
+impl PadState {
+    fn new(now: Instant) -> Self {
+        PadState {
+            held: false,
+            last_seen: now,
+            last_fired: now,
+        }
+    }
+}
+
+//process received messages from server and turns them into game inputs like shoot, move left, move right.
 pub struct MessageProcessor {
     pressure_threshold: u32,
+    release_timeout: Duration,
+    auto_repeat: Option<Duration>,
+    pad_states: HashMap<PadType, PadState>,
 }
 
 impl MessageProcessor {
     pub fn new(pressure_threshold: u32) -> Self {
         MessageProcessor {
             pressure_threshold,
+            release_timeout: Duration::from_millis(DEFAULT_RELEASE_TIMEOUT_MS),
+            auto_repeat: None,
+            pad_states: HashMap::new(),
         }
     }
 
+    //a held pad fires again every `interval` instead of only once
+    pub fn with_auto_repeat(mut self, interval: Duration) -> Self {
+        self.auto_repeat = Some(interval);
+        self
+    }
+
     pub async fn process_message(
         &self,
         rx_arc: Receiver<PressurePadData>,
@@ -27,22 +55,81 @@ impl MessageProcessor {
         rx.recv().await
     }
 
+    //drains every pending message without waiting, called once per frame
+    pub fn drain_pending(&mut self, rx: &mut Receiver<PressurePadData>) -> Vec<GameInput> {
+        let now = Instant::now();
+        let mut inputs = Vec::new();
+
+        while let Ok(message) = rx.try_recv() {
+            if self.register_message(&message, now) {
+                inputs.push(self.pad_input(&message.pad_type));
+            }
+        }
+
+        for pad_type in self.update_held_pads(now) {
+            inputs.push(self.pad_input(&pad_type));
+        }
+
+        inputs
+    }
+
     pub fn should_process_pad(&self, message: &PressurePadData) -> bool {
         message.pressure >= self.pressure_threshold as f32
     }
 
-    pub fn handle_active_pad(
-        &self,
-        pad_type: &PadType,
-        player: &mut Player,
-        grid: &mut HashMap<Cords, Box<dyn Ship>>,
-    ) {
+    //returns true only on the press edge of a pad
+    fn register_message(&mut self, message: &PressurePadData, now: Instant) -> bool {
+        let pressed = self.should_process_pad(message);
+        let state = self.pad_states
+            .entry(message.pad_type.clone())
+            .or_insert_with(|| PadState::new(now));
+
+        if !pressed {
+            state.held = false;
+            return false;
+        }
+
+        state.last_seen = now;
+
+        if state.held {
+            return false;
+        }
+
+        state.held = true;
+        state.last_fired = now;
+        true
+    }
+
+    //releases pads that stopped reporting and returns the pads due for an auto repeat
+    fn update_held_pads(&mut self, now: Instant) -> Vec<PadType> {
+        let mut repeats = Vec::new();
+
+        for (pad_type, state) in self.pad_states.iter_mut() {
+            if !state.held {
+                continue;
+            }
+
+            if now.duration_since(state.last_seen) >= self.release_timeout {
+                state.held = false;
+                continue;
+            }
+
+            if let Some(interval) = self.auto_repeat {
+                if now.duration_since(state.last_fired) >= interval {
+                    state.last_fired = now;
+                    repeats.push(pad_type.clone());
+                }
+            }
+        }
+
+        repeats
+    }
+
+    pub fn pad_input(&self, pad_type: &PadType) -> GameInput {
         match pad_type {
-            PadType::Left => player.move_left(),
-            PadType::Right => player.move_right(),
-            PadType::Shoot => {
-                player.shoot(grid);
-            },
+            PadType::Left => GameInput::MoveLeft,
+            PadType::Right => GameInput::MoveRight,
+            PadType::Shoot => GameInput::Shoot,
         }
     }
-}
\ No newline at end of file
+}
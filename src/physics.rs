@@ -0,0 +1,366 @@
+use crate::collision::{player_contact, projectile_collision, Collision, Contact};
+use crate::ship::{GridEvent, Heading, Ship, ShipGrid, ShipKind};
+use crate::ship_ai::AIContext;
+use crate::structs::{Cords, COLUMNS, ROWS};
+use std::collections::HashMap;
+use std::ops::{Add, Mul, Sub};
+use std::time::Duration;
+use uuid::Uuid;
+
+//how hard a ship's body is pulled toward the cell its ai put it in, and how much of its speed it loses doing so
+const STEERING_STIFFNESS: f32 = 90.0;
+const STEERING_DAMPING: f32 = 14.0;
+//bodies further than this many cells from their cell, like a dive wrapping back to the top, jump there
+const MAX_STEERING_DISTANCE: f32 = 2.5;
+//the broad phase buckets bodies by cell
+const BUCKET_SIZE: f32 = 1.0;
+
+//positions are in cells, x along the columns and y down the rows, so the centre of Cords(row, col) is (col + 0.5, row + 0.5)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2 { x: 0.0, y: 0.0 };
+
+    pub fn new(x: f32, y: f32) -> Self {
+        Vec2 { x, y }
+    }
+
+    pub fn length(self) -> f32 {
+        (self.x * self.x + self.y * self.y).sqrt()
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, scale: f32) -> Vec2 {
+        Vec2::new(self.x * scale, self.y * scale)
+    }
+}
+
+pub fn cell_center(cords: Cords) -> Vec2 {
+    Vec2::new(cords.1 as f32 + 0.5, cords.0 as f32 + 0.5)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Aabb {
+    pub fn around(center: Vec2, half_size: Vec2) -> Self {
+        Aabb {
+            min: center - half_size,
+            max: center + half_size,
+        }
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x < other.max.x && other.min.x < self.max.x && self.min.y < other.max.y && other.min.y < self.max.y
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Body {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub half_size: Vec2,
+}
+
+impl Body {
+    pub fn at_cell(cords: Cords, kind: ShipKind) -> Self {
+        Body {
+            position: cell_center(cords),
+            velocity: Vec2::ZERO,
+            half_size: half_size(kind),
+        }
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        Aabb::around(self.position, self.half_size)
+    }
+
+    //the cell the body's centre is in, None once it has left the board
+    pub fn cords(&self) -> Option<Cords> {
+        let (x, y) = (self.position.x.floor(), self.position.y.floor());
+        let on_board = x >= 0.0 && y >= 0.0 && (x as usize) < COLUMNS && (y as usize) < ROWS;
+        on_board.then_some(Cords(y as usize, x as usize))
+    }
+
+    //a spring toward `target`, a ship changing direction swings around instead of turning on the spot
+    fn steer(&mut self, target: Vec2, dt: f32) {
+        let offset = target - self.position;
+        if offset.length() > MAX_STEERING_DISTANCE {
+            self.position = target;
+            self.velocity = Vec2::ZERO;
+            return;
+        }
+
+        let acceleration = offset * STEERING_STIFFNESS - self.velocity * STEERING_DAMPING;
+        self.velocity = self.velocity + acceleration * dt;
+        self.position = self.position + self.velocity * dt;
+    }
+}
+
+//hitbox half sizes in cells
+fn half_size(kind: ShipKind) -> Vec2 {
+    match kind {
+        ShipKind::Enemy(_) | ShipKind::Explosion => Vec2::new(0.4, 0.4),
+        ShipKind::Projectile(_) => Vec2::new(0.12, 0.3),
+        ShipKind::TractorBeam => Vec2::new(0.45, 0.5),
+        ShipKind::PowerUp(_) => Vec2::new(0.35, 0.35),
+    }
+}
+
+const FIGHTER_HALF_SIZE: Vec2 = Vec2 { x: 0.4, y: 0.4 };
+
+//the broad phase, bodies are only tested against the ones sharing a bucket with them
+#[derive(Default)]
+struct SpatialHash {
+    buckets: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialHash {
+    fn bucket_range(aabb: &Aabb) -> impl Iterator<Item = (i32, i32)> {
+        let (min_x, max_x) = ((aabb.min.x / BUCKET_SIZE).floor() as i32, (aabb.max.x / BUCKET_SIZE).floor() as i32);
+        let (min_y, max_y) = ((aabb.min.y / BUCKET_SIZE).floor() as i32, (aabb.max.y / BUCKET_SIZE).floor() as i32);
+        (min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+    }
+
+    fn insert(&mut self, index: usize, aabb: &Aabb) {
+        for bucket in Self::bucket_range(aabb) {
+            self.buckets.entry(bucket).or_default().push(index);
+        }
+    }
+
+    //every index sharing a bucket with `aabb`, in the order they were inserted so collisions resolve the same way every run
+    fn query(&self, aabb: &Aabb) -> Vec<usize> {
+        let mut found: Vec<usize> = Self::bucket_range(aabb)
+            .filter_map(|bucket| self.buckets.get(&bucket))
+            .flatten()
+            .copied()
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+}
+
+//a projectile flying free of the grid
+struct FreeProjectile {
+    ship: Box<dyn Ship>,
+    body: Body,
+}
+
+enum Collider {
+    Ship(Cords),
+    Projectile(usize),
+    //one of the fighter's cells
+    Fighter(Cords),
+}
+
+//the physics game mode, projectiles leave the grid and fly on their own while every other ship gets a body that
+//swings after the cell its ai picks, hits are decided by overlapping hitboxes instead of shared cells
+#[derive(Default)]
+pub struct PhysicsWorld {
+    bodies: HashMap<Uuid, Body>,
+    projectiles: Vec<FreeProjectile>,
+}
+
+impl PhysicsWorld {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.bodies.clear();
+        self.projectiles.clear();
+    }
+
+    //where a ship on the grid is right now
+    pub fn position(&self, ship_id: Uuid) -> Option<Vec2> {
+        self.bodies.get(&ship_id).map(|body| body.position)
+    }
+
+    pub fn projectiles(&self) -> impl Iterator<Item = (&dyn Ship, Vec2)> {
+        self.projectiles.iter().map(|projectile| (projectile.ship.as_ref(), projectile.body.position))
+    }
+
+    pub fn player_bullet_count(&self) -> usize {
+        self.projectiles.iter().filter(|projectile| projectile.ship.is_player_bullet()).count()
+    }
+
+    pub fn step(&mut self, ship_grid: &mut ShipGrid, ctx: &AIContext, fighter_cells: &[Cords]) {
+        let dt = ctx.dt.as_secs_f32();
+
+        self.lift_projectiles(ship_grid, ctx);
+        self.steer_ships(ship_grid, dt);
+
+        for projectile in &mut self.projectiles {
+            projectile.body.position = projectile.body.position + projectile.body.velocity * dt;
+        }
+        self.projectiles.retain(|projectile| projectile.body.cords().is_some());
+
+        self.resolve_collisions(ship_grid, fighter_cells);
+    }
+
+    //takes every projectile off the grid, it keeps flying at the speed it would have moved from cell to cell
+    fn lift_projectiles(&mut self, ship_grid: &mut ShipGrid, ctx: &AIContext) {
+        let mut lifted: Vec<Cords> = ship_grid.grid
+            .iter()
+            .filter(|(_, ship)| ship.is_projectile())
+            .map(|(&cords, _)| cords)
+            .collect();
+        lifted.sort();
+
+        for cords in lifted {
+            let Some(ship) = ship_grid.grid.remove(&cords) else {
+                continue;
+            };
+
+            let interval = ship.move_interval().unwrap_or(ctx.settings.value_stats.laser_speed).max(Duration::from_millis(1));
+            let cells_per_second = ctx.speed / interval.as_secs_f32();
+            let direction = match ship.heading() {
+                Some(Heading::Up) => -1.0,
+                Some(Heading::Down) | None => 1.0,
+            };
+
+            let mut body = Body::at_cell(cords, ship.kind());
+            body.velocity = Vec2::new(0.0, direction * cells_per_second);
+            self.projectiles.push(FreeProjectile { ship, body });
+        }
+    }
+
+    fn steer_ships(&mut self, ship_grid: &ShipGrid, dt: f32) {
+        let mut alive = HashMap::new();
+        for (&cords, ship) in &ship_grid.grid {
+            let mut body = self.bodies.remove(&ship.get_id()).unwrap_or_else(|| Body::at_cell(cords, ship.kind()));
+            body.steer(cell_center(cords), dt);
+            alive.insert(ship.get_id(), body);
+        }
+        self.bodies = alive;
+    }
+
+    fn resolve_collisions(&mut self, ship_grid: &mut ShipGrid, fighter_cells: &[Cords]) {
+        let mut ships: Vec<(Cords, Uuid)> = ship_grid.grid.iter().map(|(&cords, ship)| (cords, ship.get_id())).collect();
+        ships.sort();
+
+        let mut colliders: Vec<(Collider, Aabb)> = Vec::new();
+        for (cords, ship_id) in ships {
+            if let Some(body) = self.bodies.get(&ship_id) {
+                colliders.push((Collider::Ship(cords), body.aabb()));
+            }
+        }
+        for (index, projectile) in self.projectiles.iter().enumerate() {
+            colliders.push((Collider::Projectile(index), projectile.body.aabb()));
+        }
+        for &cords in fighter_cells {
+            colliders.push((Collider::Fighter(cords), Aabb::around(cell_center(cords), FIGHTER_HALF_SIZE)));
+        }
+
+        let mut spatial_hash = SpatialHash::default();
+        for (index, (_, aabb)) in colliders.iter().enumerate() {
+            spatial_hash.insert(index, aabb);
+        }
+
+        let mut spent = vec![false; self.projectiles.len()];
+        for index in 0..self.projectiles.len() {
+            if spent[index] {
+                continue;
+            }
+
+            let aabb = self.projectiles[index].body.aabb();
+            for candidate in spatial_hash.query(&aabb) {
+                let (collider, other) = &colliders[candidate];
+                if !aabb.overlaps(other) {
+                    continue;
+                }
+
+                let projectile = self.projectiles[index].ship.as_ref();
+                let used_up = match collider {
+                    Collider::Projectile(other) if *other == index || spent[*other] => false,
+                    Collider::Projectile(other) => {
+                        // bullets of the same side fly through each other instead of waiting
+                        let cancel = projectile_collision(projectile, self.projectiles[*other].ship.as_ref()) == Collision::Cancel;
+                        if cancel {
+                            spent[*other] = true;
+                        }
+                        cancel
+                    }
+                    Collider::Ship(cords) => match ship_grid.grid.get(cords) {
+                        Some(target) => match projectile_collision(projectile, target.as_ref()) {
+                            Collision::Hit => {
+                                ship_grid.hit_ship(projectile, *cords);
+                                true
+                            }
+                            Collision::Absorb => true,
+                            Collision::Cancel => {
+                                ship_grid.grid.remove(cords);
+                                true
+                            }
+                            Collision::Pass | Collision::Block => false,
+                        },
+                        None => false,
+                    },
+                    Collider::Fighter(fighter) => {
+                        let crash = player_contact(projectile) == Contact::Crash;
+                        if crash {
+                            ship_grid.events.push(GridEvent::FighterStruck(*fighter));
+                        }
+                        crash
+                    }
+                };
+
+                if used_up {
+                    spent[index] = true;
+                    break;
+                }
+            }
+        }
+
+        let mut index = 0;
+        self.projectiles.retain(|_| {
+            index += 1;
+            !spent[index - 1]
+        });
+
+        // enemies, beams and drops reach the fighter with their bodies, the game decides what touching each one does
+        let mut touched = Vec::new();
+        for (collider, aabb) in &colliders {
+            let Collider::Fighter(fighter) = collider else {
+                continue;
+            };
+            for candidate in spatial_hash.query(aabb) {
+                let (Collider::Ship(cords), other) = &colliders[candidate] else {
+                    continue;
+                };
+                let contact = ship_grid.grid.get(cords).map_or(Contact::Ignore, |ship| player_contact(ship.as_ref()));
+                if contact != Contact::Ignore && aabb.overlaps(other) && !touched.contains(cords) {
+                    touched.push(*cords);
+                    ship_grid.events.push(GridEvent::FighterTouched { ship: *cords, fighter: *fighter });
+                }
+            }
+        }
+    }
+}
@@ -1,8 +1,11 @@
-use crate::ship::{Ship, new_bullet_ship};
+use crate::power_up::{ActivePowerUps, PowerUpKind, MAX_LIVES};
+use crate::ship::{Ship, new_player_bullet};
 use crate::structs::{Cords, Timer, COLUMNS, ROWS};
 use crate::settings::Settings;
+use crate::weapon::{Weapon, WeaponLibrary};
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use std::time::{Instant, Duration};
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct Player {
@@ -14,7 +17,11 @@ pub struct Player {
     pub is_dead: bool,
     pub blink_timer: Timer,
     pub blink_count: u8,
-    pub last_shot_time: Option<Instant>,  // Track when the last shot was fired
+    pub shot_cooldown: Duration,  // Time left until the player can shoot again
+    pub dual_fighter: bool,  // a rescued fighter flies in the column to the right
+    lost_fighter: bool,  // one of the two fighters went down this step, a second hit in the same step lands on its wreck
+    pub power_ups: ActivePowerUps,
+    pub weapon: Weapon,  // the chosen weapon, a weapon power-up replaces it while it runs
 }
 
 impl Player {
@@ -29,7 +36,11 @@ impl Player {
             is_dead: false,
             blink_timer: Timer::new_with_duration(50, 15, "blink_timer"),
             blink_count: 0,
-            last_shot_time: None,
+            shot_cooldown: Duration::ZERO,
+            dual_fighter: false,
+            lost_fighter: false,
+            power_ups: ActivePowerUps::default(),
+            weapon: Weapon::default(),
         }
     }
 
@@ -50,40 +61,65 @@ impl Player {
             return;
         }
 
+        let last_column = if self.dual_fighter { COLUMNS - 2 } else { COLUMNS - 1 };
         if let Some(current_pos) = &mut self.current_position {
-            if current_pos.1 < COLUMNS - 1 {
+            if current_pos.1 < last_column {
                 current_pos.1 += 1;
             }
         }
     }
 
-    pub fn shoot(&mut self, grid: &mut HashMap<Cords, Box<dyn Ship>>) -> bool {
-        if self.is_dead {
+    //`on_screen` is how many of the player's bullets are already flying
+    pub fn shoot(&mut self, grid: &mut HashMap<Cords, Box<dyn Ship>>, on_screen: usize, weapons: &WeaponLibrary) -> bool {
+        if self.is_dead || !self.shot_cooldown.is_zero() {
             return false;
         }
 
-        // Check if enough time has passed since the last shot (200ms cooldown)
-        let now = Instant::now();
-        if let Some(last_time) = self.last_shot_time {
-            if now.duration_since(last_time) < Duration::from_millis(2000) {
-                return false; // Not enough time has passed
+        let weapon = self.active_weapon(weapons).clone();
+        let mut bullets_left = weapon.bullets_left(on_screen);
+
+        let mut fired = false;
+        for pos in self.cells() {
+            for bullet_coords in weapon.bullet_cells(pos) {
+                if bullets_left == 0 {
+                    break;
+                }
+                if let Entry::Vacant(cell) = grid.entry(bullet_coords) {
+                    cell.insert(new_player_bullet(weapon.projectile, weapon.bullet_speed()));
+                    bullets_left -= 1;
+                    fired = true;
+                }
             }
         }
 
+        if fired {
+            self.shot_cooldown = weapon.cooldown();
+        }
+        fired
+    }
+
+    //the weapon shots use right now, the first running weapon power-up takes over from the chosen one
+    pub fn active_weapon<'a>(&'a self, weapons: &'a WeaponLibrary) -> &'a Weapon {
+        PowerUpKind::ALL
+            .iter()
+            .filter(|&&power_up| self.power_ups.is_active(power_up))
+            .find_map(|power_up| power_up.weapon().and_then(|name| weapons.get(name)))
+            .unwrap_or(&self.weapon)
+    }
+
+    pub fn collect(&mut self, power_up: PowerUpKind) {
+        match power_up {
+            PowerUpKind::ExtraLife => self.lives = (self.lives + 1).min(MAX_LIVES),
+            _ => self.power_ups.activate(power_up),
+        }
+    }
+
+    //the cells the fighter covers, two side by side in dual fighter mode
+    pub fn cells(&self) -> Vec<Cords> {
         match self.current_position {
-            Some(pos) => {
-                let bullet_coords = Cords(pos.0.saturating_sub(1), pos.1);
-                if grid.contains_key(&bullet_coords) {
-                    false
-                } else {
-                    let bullet = new_bullet_ship(false);
-                    grid.insert(bullet_coords, bullet);
-                    // Update the last shot time
-                    self.last_shot_time = Some(now);
-                    true
-                }
-            }
-            None => false,
+            Some(pos) if self.dual_fighter => vec![pos, Cords(pos.0, pos.1 + 1)],
+            Some(pos) => vec![pos],
+            None => Vec::new(),
         }
     }
 
@@ -92,26 +128,60 @@ impl Player {
             return false;
         }
 
-        let is_fly = match grid.get(&pos) {
-            Some(ship) => ship.display_type() == "fly",
-            None => false
-        };
+        let collided_score = grid.get(&pos).map_or(0, |ship| ship.score_value());
+        if self.struck(pos, false) {
+            return true;
+        }
 
-        let remaining_lives = self.decrease_lives();
+        *score += collided_score;
+        grid.remove(&pos);
+        false
+    }
 
-        match remaining_lives {
-            Some(5) => true,
-            Some(_) => {
-                if is_fly {
-                    *score += 100;
+    //returns true when the hit cost the last life, `cell` is the fighter cell that was hit
+    pub fn struck(&mut self, cell: Cords, invincible: bool) -> bool {
+        if self.is_dead || invincible {
+            return false;
+        }
+
+        // with two fighters only the one that was hit is lost, the other one flies on from its own cell
+        if self.dual_fighter {
+            self.dual_fighter = false;
+            self.lost_fighter = true;
+            if let Some(pos) = &mut self.current_position {
+                if *pos == cell {
+                    pos.1 += 1;
                 }
-                grid.remove(&pos);
-                false
-            },
-            None => false
+            }
+            return false;
         }
+
+        // both fighters hit at once only cost the pair, not a life as well
+        if self.lost_fighter {
+            return false;
+        }
+
+        self.decrease_lives().is_none()
     }
 
+    //the freed fighter joins on the right, so make room for it at the edge of the board
+    pub fn rescue_fighter(&mut self) {
+        self.dual_fighter = true;
+        if let Some(pos) = &mut self.current_position {
+            pos.1 = pos.1.min(COLUMNS - 2);
+        }
+    }
+
+    //a tractor beam took the fighter, it costs a life the same way a crash does
+    pub fn capture(&mut self) -> bool {
+        if self.is_dead {
+            return false;
+        }
+        self.dual_fighter = false;
+        self.decrease_lives().is_none()
+    }
+
+    //the lives left, None when that was the last one and the player starts over
     pub fn decrease_lives(&mut self) -> Option<u8> {
         self.lives -= 1;
 
@@ -119,10 +189,11 @@ impl Player {
         self.current_position = None;
         self.blink_count = 0;
         self.blink_timer.reset();
+        self.power_ups.clear();
 
         if self.lives == 0 {
             self.reset();
-            Some(5)
+            None
         } else {
             Some(self.lives)
         }
@@ -135,12 +206,18 @@ impl Player {
         self.blink_count = 0;
         self.blink_timer.reset();
         self.movement_direction = 1;
-        self.last_shot_time = None;  // Reset the last shot time when player resets
+        self.shot_cooldown = Duration::ZERO;  // Reset the cooldown when player resets
+        self.dual_fighter = false;
+        self.power_ups.clear();
     }
 
-    pub fn update(&mut self) {
+    pub fn update(&mut self, dt: Duration) {
+        self.lost_fighter = false;
+        self.shot_cooldown = self.shot_cooldown.saturating_sub(dt);
+        self.power_ups.tick(dt);
+
         if self.is_dead {
-            if self.blink_timer.tick() {
+            if self.blink_timer.tick(dt) {
                 if self.current_position.is_none() {
                     self.current_position = Some(self.start_position);
                 } else {
@@ -0,0 +1,133 @@
+use crate::settings::Settings;
+use crate::ship::{Ship, ShipKind};
+use crate::ship_ai::{AIAction, AIContext, ShipAI};
+use crate::structs::{Cords, RelCords, ShipAction};
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::collections::HashMap;
+use std::time::Duration;
+use uuid::Uuid;
+
+//how long a drop takes to fall one row
+const DROP_FALL_SPEED: Duration = Duration::from_millis(250);
+
+pub const MAX_LIVES: u8 = 9;
+pub const SCORE_MULTIPLIER: u32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PowerUpKind {
+    RapidFire,
+    SpreadShot,
+    Shield,
+    ExtraLife,
+    ScoreMultiplier,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 5] = [
+        PowerUpKind::RapidFire,
+        PowerUpKind::SpreadShot,
+        PowerUpKind::Shield,
+        PowerUpKind::ExtraLife,
+        PowerUpKind::ScoreMultiplier,
+    ];
+
+    //how long it lasts once picked up, zero for the ones that apply once
+    pub fn duration(self) -> Duration {
+        match self {
+            PowerUpKind::RapidFire => Duration::from_secs(10),
+            PowerUpKind::SpreadShot => Duration::from_secs(10),
+            PowerUpKind::Shield => Duration::from_secs(6),
+            PowerUpKind::ExtraLife => Duration::ZERO,
+            PowerUpKind::ScoreMultiplier => Duration::from_secs(15),
+        }
+    }
+
+    //the weapon from the weapon library the fighter shoots with while it runs
+    pub fn weapon(self) -> Option<&'static str> {
+        match self {
+            PowerUpKind::RapidFire => Some("rapid"),
+            PowerUpKind::SpreadShot => Some("spread"),
+            _ => None,
+        }
+    }
+
+    pub fn random(rng: &mut StdRng) -> Self {
+        Self::ALL[rng.random_range(0..Self::ALL.len())]
+    }
+}
+
+//a power-up falling from where an enemy was destroyed, the player picks it up by being in its cell
+pub struct PowerUpShip {
+    ai: ShipAI,
+    id: Uuid,
+    power_up: PowerUpKind,
+}
+
+impl PowerUpShip {
+    pub fn new(power_up: PowerUpKind) -> Self {
+        Self {
+            ai: ShipAI::new(
+                vec![AIAction::new_await(AIAction::RelativeMove(RelCords(1, 0)), |_: &Settings| DROP_FALL_SPEED)]
+            ),
+            id: Uuid::new_v4(),
+            power_up,
+        }
+    }
+}
+
+impl Ship for PowerUpShip {
+    fn kind(&self) -> ShipKind {
+        ShipKind::PowerUp(self.power_up)
+    }
+
+    fn move_interval(&self) -> Option<Duration> {
+        Some(DROP_FALL_SPEED)
+    }
+
+    fn get_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn get_action(&mut self, cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>, ctx: &AIContext, rng: &mut StdRng) -> ShipAction {
+        self.ai.get_action(cords, game_board, ctx, rng)
+    }
+}
+
+pub fn new_power_up_ship(power_up: PowerUpKind) -> Box<dyn Ship> {
+    Box::new(PowerUpShip::new(power_up))
+}
+
+//the timed power-ups the player has right now and how long each one has left
+#[derive(Clone, Debug, Default)]
+pub struct ActivePowerUps {
+    remaining: HashMap<PowerUpKind, Duration>,
+}
+
+impl ActivePowerUps {
+    //picking up one that is already running starts its timer over
+    pub fn activate(&mut self, power_up: PowerUpKind) {
+        if !power_up.duration().is_zero() {
+            self.remaining.insert(power_up, power_up.duration());
+        }
+    }
+
+    pub fn is_active(&self, power_up: PowerUpKind) -> bool {
+        self.remaining.contains_key(&power_up)
+    }
+
+    pub fn remaining(&self, power_up: PowerUpKind) -> Option<Duration> {
+        self.remaining.get(&power_up).copied()
+    }
+
+    pub fn tick(&mut self, dt: Duration) {
+        for remaining in self.remaining.values_mut() {
+            *remaining = remaining.saturating_sub(dt);
+        }
+        self.remaining.retain(|_, remaining| !remaining.is_zero());
+    }
+
+    pub fn clear(&mut self) {
+        self.remaining.clear();
+    }
+}
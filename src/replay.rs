@@ -0,0 +1,231 @@
+use crate::enemy::EnemyCatalog;
+use crate::game_state::{GameInput, GameMode, GameState};
+use crate::level::LevelLibrary;
+use crate::settings::Settings;
+use crate::structs::{fingerprint, EMPTY_FINGERPRINT};
+use crate::weapon::WeaponLibrary;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+//bump this whenever GameInput or the simulation changes in a way that breaks old replays
+pub const REPLAY_VERSION: u32 = 16;
+
+//A replay file is JSON lines, a ReplayHeader on the first line followed by one ReplayEvent per line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    pub version: u32,
+    pub seed: u64,
+    pub step_ms: u64,
+    #[serde(default)]
+    pub mode: GameMode,
+    //the enemies, ai scripts, levels and weapons the replay was recorded with, see content_fingerprint
+    pub content: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayEvent {
+    pub step: u64,
+    pub time_ms: u64,
+    pub input: GameInput,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Empty,
+    Parse { line: usize, message: String },
+    UnsupportedVersion(u32),
+    ContentMismatch { recorded: u64, loaded: u64 },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "{}", err),
+            ReplayError::Empty => write!(f, "replay file is empty"),
+            ReplayError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            ReplayError::UnsupportedVersion(version) => {
+                write!(f, "replay version {} is not supported (expected {})", version, REPLAY_VERSION)
+            }
+            ReplayError::ContentMismatch { recorded, loaded } => {
+                write!(f, "replay was recorded with different enemies, ai scripts, levels or weapons ({:016x}, loaded {:016x})", recorded, loaded)
+            }
+        }
+    }
+}
+
+//one value for every content file the simulation reads, a replay only plays back against the same files
+pub fn content_fingerprint(enemies: &EnemyCatalog, levels: &LevelLibrary, weapons: &WeaponLibrary) -> u64 {
+    [enemies.fingerprint(), levels.fingerprint(), weapons.fingerprint()]
+        .iter()
+        .fold(EMPTY_FINGERPRINT, |hash, part| fingerprint(hash, &part.to_le_bytes()))
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub events: Vec<ReplayEvent>,
+}
+
+impl Replay {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut lines = reader.lines().enumerate();
+
+        let header: ReplayHeader = match lines.next() {
+            Some((_, line)) => parse_line(&line?, 1)?,
+            None => return Err(ReplayError::Empty),
+        };
+
+        if header.version != REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(header.version));
+        }
+
+        let mut events = Vec::new();
+        for (index, line) in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            events.push(parse_line(&line, index + 1)?);
+        }
+
+        Ok(Replay { header, events })
+    }
+
+    //refuses content that differs from what the replay was recorded with, it would play out differently
+    pub fn check_content(&self, enemies: &EnemyCatalog, levels: &LevelLibrary, weapons: &WeaponLibrary) -> Result<(), ReplayError> {
+        let loaded = content_fingerprint(enemies, levels, weapons);
+        if loaded != self.header.content {
+            return Err(ReplayError::ContentMismatch { recorded: self.header.content, loaded });
+        }
+        Ok(())
+    }
+
+    pub fn step_duration(&self) -> Duration {
+        Duration::from_millis(self.header.step_ms)
+    }
+
+    //runs the replay headless for `steps` steps, used to reproduce sessions and as regression fixtures
+    pub fn simulate(&self, enemies: Arc<EnemyCatalog>, levels: Arc<LevelLibrary>, weapons: Arc<WeaponLibrary>, steps: u64) -> GameState {
+        let mut game = GameState::new(Settings::new(), self.header.seed, enemies, levels, weapons, self.header.mode);
+        let mut player = ReplayPlayer::new(self.clone());
+
+        for _ in 0..steps {
+            let inputs = player.inputs_for(game.steps());
+            game.step(self.step_duration(), &inputs);
+        }
+
+        game
+    }
+}
+
+fn parse_line<T: for<'de> Deserialize<'de>>(line: &str, line_number: usize) -> Result<T, ReplayError> {
+    serde_json::from_str(line).map_err(|err| ReplayError::Parse {
+        line: line_number,
+        message: err.to_string(),
+    })
+}
+
+//appends every step's inputs to a replay file as the game runs
+pub struct ReplayRecorder {
+    file: File,
+    path: PathBuf,
+    step_ms: u64,
+}
+
+impl ReplayRecorder {
+    pub fn create(dir: impl AsRef<Path>, seed: u64, step: Duration, mode: GameMode, content: u64) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        let path = dir.as_ref().join(format!("replay_{}_{}.jsonl", started, seed));
+
+        let file = OpenOptions::new().create_new(true).append(true).open(&path)?;
+        let mut recorder = ReplayRecorder {
+            file,
+            path,
+            step_ms: step.as_millis() as u64,
+        };
+
+        let header = ReplayHeader {
+            version: REPLAY_VERSION,
+            seed,
+            step_ms: recorder.step_ms,
+            mode,
+            content,
+        };
+        recorder.write_line(&header)?;
+
+        Ok(recorder)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&mut self, step: u64, inputs: &[GameInput]) -> io::Result<()> {
+        for input in inputs {
+            let event = ReplayEvent {
+                step,
+                time_ms: step * self.step_ms,
+                input: input.clone(),
+            };
+            self.write_line(&event)?;
+        }
+        Ok(())
+    }
+
+    fn write_line<T: Serialize>(&mut self, value: &T) -> io::Result<()> {
+        let line = serde_json::to_string(value)?;
+        writeln!(self.file, "{}", line)
+    }
+}
+
+//hands a replay's inputs back out step by step instead of reading the pressure pads
+pub struct ReplayPlayer {
+    replay: Replay,
+    cursor: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayer { replay, cursor: 0 }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.replay.header.seed
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.cursor >= self.replay.events.len()
+    }
+
+    pub fn inputs_for(&mut self, step: u64) -> Vec<GameInput> {
+        let mut inputs = Vec::new();
+
+        while let Some(event) = self.replay.events.get(self.cursor) {
+            if event.step > step {
+                break;
+            }
+            inputs.push(event.input.clone());
+            self.cursor += 1;
+        }
+
+        inputs
+    }
+}
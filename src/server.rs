@@ -1,10 +1,15 @@
 use std::convert::Infallible;
-use std::net::IpAddr;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::str::FromStr;
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, watch, RwLock};
+use warp::http::StatusCode;
 use warp::Filter;
 use serde::{Deserialize, Serialize};
+use crate::weapon::Weapon;
 
 //this code creates a Warp server this is where the microcontrollers connect and pass messages (E.g. shoot, left, right)
 
@@ -23,15 +28,160 @@ pub struct PressurePadData {
     pub pad_type: PadType,
 }
 
+const SERVER_CONFIG_PATH: &str = "server_config.json";
+const SERVER_CONFIG_PATH_ENV: &str = "GALAGA_SERVER_CONFIG";
+const SERVER_ENABLED_ENV: &str = "GALAGA_SERVER_ENABLED";
+const SERVER_ADDRESS_ENV: &str = "GALAGA_SERVER_ADDRESS";
+const SERVER_PORT_ENV: &str = "GALAGA_SERVER_PORT";
+
+//where the pad server listens, loaded from server_config.json and then overridden by env variables
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub enabled: bool,
+    pub bind_address: String,
+    pub port: u16,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            enabled: true,
+            bind_address: Ipv4Addr::UNSPECIFIED.to_string(),
+            port: 8080,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn load() -> Self {
+        let path = env::var(SERVER_CONFIG_PATH_ENV).unwrap_or_else(|_| SERVER_CONFIG_PATH.to_string());
+        let mut config = Self::from_file(&path).unwrap_or_default();
+        config.apply_env();
+        config
+    }
+
+    fn from_file(path: &str) -> Option<Self> {
+        let contents = fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&contents) {
+            Ok(config) => Some(config),
+            Err(err) => {
+                eprintln!("Ignoring invalid server config {}: {}", path, err);
+                None
+            }
+        }
+    }
+
+    fn apply_env(&mut self) {
+        if let Ok(enabled) = env::var(SERVER_ENABLED_ENV) {
+            self.enabled = !matches!(enabled.to_lowercase().as_str(), "0" | "false" | "off" | "no");
+        }
+        if let Ok(address) = env::var(SERVER_ADDRESS_ENV) {
+            self.bind_address = address;
+        }
+        if let Ok(port) = env::var(SERVER_PORT_ENV) {
+            match port.parse() {
+                Ok(port) => self.port = port,
+                Err(_) => eprintln!("Ignoring invalid {}: {}", SERVER_PORT_ENV, port),
+            }
+        }
+    }
+
+    //configured address first, then every interface, then localhost
+    fn bind_candidates(&self) -> Vec<SocketAddr> {
+        let mut candidates = Vec::new();
+
+        match IpAddr::from_str(&self.bind_address) {
+            Ok(ip) => candidates.push(SocketAddr::new(ip, self.port)),
+            Err(_) => eprintln!("Ignoring invalid bind address: {}", self.bind_address),
+        }
+
+        for ip in [Ipv4Addr::UNSPECIFIED, Ipv4Addr::LOCALHOST] {
+            let addr = SocketAddr::new(IpAddr::V4(ip), self.port);
+            if !candidates.contains(&addr) {
+                candidates.push(addr);
+            }
+        }
+
+        candidates
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerStatus {
+    Starting,
+    Disabled,
+    Running(SocketAddr),
+    Failed(String),
+}
+
+impl fmt::Display for ServerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerStatus::Starting => write!(f, "Server: starting"),
+            ServerStatus::Disabled => write!(f, "Server: disabled"),
+            ServerStatus::Running(addr) => write!(f, "Server: {}", addr),
+            ServerStatus::Failed(err) => write!(f, "Server: {}", err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct DifficultyConfig {
     pub difficulty_percentage: f32,
     pub drop_percentage: f32,
 }
 
+impl Default for DifficultyConfig {
+    fn default() -> Self {
+        DifficultyConfig {
+            difficulty_percentage: 50.0,
+            drop_percentage: 10.0,
+        }
+    }
+}
+
+//the settings values the game is actually running with, written back by the game loop every tick
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct AppliedDifficulty {
+    pub fly_speed_ms: u64,
+    pub shooting_randomness: u32,
+    pub fly_count_multiplier: f32,
+    pub drop_percentage: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DifficultyState {
+    #[serde(flatten)]
+    pub config: DifficultyConfig,
+    pub applied: AppliedDifficulty,
+}
+
+//a weapon asked for over http, {"named": "spread"} picks one from the weapon library, {"custom": {...}} sends a whole new one
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WeaponRequest {
+    Named(String),
+    Custom(Weapon),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct WeaponState {
+    //the weapons in the library, filled in once the game has loaded them
+    pub available: Vec<Weapon>,
+    //the weapon the player has chosen, written back by the game loop every tick
+    pub current: Weapon,
+    //what shots use right now, differs from current while a weapon power-up runs
+    pub active: Weapon,
+    //set by a PUT and taken by the game loop on its next tick
+    #[serde(skip)]
+    pub requested: Option<Weapon>,
+}
+
 pub fn routes(
     tx: mpsc::Sender<PressurePadData>,
-    difficulty_config: Arc<RwLock<DifficultyConfig>>,
+    difficulty_state: Arc<RwLock<DifficultyState>>,
+    weapon_state: Arc<RwLock<WeaponState>>,
 ) -> impl warp::Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     let pressure_route = warp::path("pressure")
         .and(warp::post())
@@ -50,13 +200,13 @@ pub fn routes(
         .and(warp::get())
         .map(|| "Server is running!");
 
-    let difficulty_config_clone = difficulty_config.clone();
+    let difficulty_state_clone = difficulty_state.clone();
     let difficulty_route = warp::path("difficulty")
         .and(warp::get())
         .and_then(move || {
-            let config = difficulty_config_clone.clone();
+            let state = difficulty_state_clone.clone();
             async move {
-                let difficulty_data = config.read().await.clone();
+                let difficulty_data = state.read().await.clone();
                 Ok::<_, warp::Rejection>(warp::reply::json(&difficulty_data))
             }
         });
@@ -64,50 +214,117 @@ pub fn routes(
     let update_difficulty_route = warp::path("difficulty")
         .and(warp::put())
         .and(warp::body::json())
-        .and(with_difficulty_config(difficulty_config.clone()))
+        .and(with_difficulty_state(difficulty_state.clone()))
         .and_then(
-            |new_config: DifficultyConfig, config: Arc<RwLock<DifficultyConfig>>| async move {
+            |new_config: DifficultyConfig, state: Arc<RwLock<DifficultyState>>| async move {
                 let mut validated_config = new_config.clone();
 
                 validated_config.difficulty_percentage = validated_config.difficulty_percentage.clamp(0.0, 100.0);
                 validated_config.drop_percentage = validated_config.drop_percentage.clamp(0.0, 100.0);
 
-                let mut config_write = config.write().await;
-                *config_write = validated_config.clone();
+                let mut state_write = state.write().await;
+                state_write.config = validated_config.clone();
 
-                println!("Updated difficulty percentage to: {}%", config_write.difficulty_percentage);
-                println!("Updated drop percentage to: {}%", config_write.drop_percentage);
+                println!("Updated difficulty percentage to: {}%", state_write.config.difficulty_percentage);
+                println!("Updated drop percentage to: {}%", state_write.config.drop_percentage);
 
                 Ok::<_, warp::Rejection>(warp::reply::json(&validated_config))
             },
         );
 
+    let weapon_state_clone = weapon_state.clone();
+    let weapon_route = warp::path("weapon")
+        .and(warp::get())
+        .and_then(move || {
+            let state = weapon_state_clone.clone();
+            async move {
+                let weapon_data = state.read().await.clone();
+                Ok::<_, warp::Rejection>(warp::reply::json(&weapon_data))
+            }
+        });
+
+    let update_weapon_route = warp::path("weapon")
+        .and(warp::put())
+        .and(warp::body::json())
+        .and(with_weapon_state(weapon_state))
+        .and_then(
+            |request: WeaponRequest, state: Arc<RwLock<WeaponState>>| async move {
+                let mut state_write = state.write().await;
+
+                let weapon = match request {
+                    WeaponRequest::Named(name) => state_write
+                        .available
+                        .iter()
+                        .find(|weapon| weapon.name == name)
+                        .cloned()
+                        .ok_or_else(|| format!("unknown weapon `{}`", name)),
+                    WeaponRequest::Custom(weapon) => weapon.validate().map(|_| weapon),
+                };
+
+                match weapon {
+                    Ok(weapon) => {
+                        println!("Requested weapon: {}", weapon.name);
+                        state_write.requested = Some(weapon.clone());
+                        Ok::<_, warp::Rejection>(warp::reply::with_status(warp::reply::json(&weapon), StatusCode::OK))
+                    }
+                    Err(message) => Ok(warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({ "error": message })),
+                        StatusCode::BAD_REQUEST,
+                    )),
+                }
+            },
+        );
+
     pressure_route
         .or(health_route)
         .or(difficulty_route)
         .or(update_difficulty_route)
+        .or(weapon_route)
+        .or(update_weapon_route)
 }
 
-fn with_difficulty_config(
-    config: Arc<RwLock<DifficultyConfig>>,
-) -> impl Filter<Extract = (Arc<RwLock<DifficultyConfig>>,), Error = Infallible> + Clone {
-    warp::any().map(move || config.clone())
+fn with_difficulty_state(
+    state: Arc<RwLock<DifficultyState>>,
+) -> impl Filter<Extract = (Arc<RwLock<DifficultyState>>,), Error = Infallible> + Clone {
+    warp::any().map(move || state.clone())
 }
 
-pub async fn run_server(tx: mpsc::Sender<PressurePadData>) {
-    println!("server running");
-    let ip = IpAddr::from_str("192.168.1.113").unwrap();
-    let port = 8080;
-    println!("Starting Arduino server on {}:{}", ip, port);
+fn with_weapon_state(
+    state: Arc<RwLock<WeaponState>>,
+) -> impl Filter<Extract = (Arc<RwLock<WeaponState>>,), Error = Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+pub async fn run_server(
+    tx: mpsc::Sender<PressurePadData>,
+    config: ServerConfig,
+    difficulty_state: Arc<RwLock<DifficultyState>>,
+    weapon_state: Arc<RwLock<WeaponState>>,
+    status: watch::Sender<ServerStatus>,
+) {
+    if !config.enabled {
+        println!("Arduino server disabled");
+        let _ = status.send(ServerStatus::Disabled);
+        return;
+    }
 
-    let difficulty_config = Arc::new(RwLock::new(DifficultyConfig {
-        difficulty_percentage: 50.0,
-        drop_percentage: 10.0,
-    }));
+    let routes = routes(tx, difficulty_state, weapon_state);
 
-    let routes = routes(tx, difficulty_config);
+    let mut last_error = String::from("no address to bind");
+    for addr in config.bind_candidates() {
+        match warp::serve(routes.clone()).try_bind_ephemeral(addr) {
+            Ok((bound_addr, server)) => {
+                println!("Starting Arduino server on {}", bound_addr);
+                let _ = status.send(ServerStatus::Running(bound_addr));
+                server.await;
+                return;
+            }
+            Err(err) => {
+                eprintln!("Failed to bind Arduino server on {}: {}", addr, err);
+                last_error = format!("bind {} failed", addr);
+            }
+        }
+    }
 
-    warp::serve(routes)
-        .run((ip, port))
-        .await;
-}
\ No newline at end of file
+    let _ = status.send(ServerStatus::Failed(last_error));
+}
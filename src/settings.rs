@@ -1,32 +1,103 @@
+use std::env;
+use std::fs;
 use std::time::Duration;
-use rust_on_rails::prelude::*;
-use rust_on_rails::canvas::{Area, CanvasItem, Shape, Text};
+use serde::{Deserialize, Serialize};
+use crate::game_state::GameMode;
+use crate::server::{AppliedDifficulty, DifficultyConfig};
+
+const GAME_CONFIG_PATH: &str = "game_config.json";
+const GAME_CONFIG_PATH_ENV: &str = "GALAGA_GAME_CONFIG";
+const SEED_ENV: &str = "GALAGA_SEED";
+const REPLAY_FILE_ENV: &str = "GALAGA_REPLAY";
+
+//startup options for the game itself, loaded from game_config.json and then overridden by env variables
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    pub seed: Option<u64>,
+    pub record_replays: bool,
+    pub replay_dir: String,
+    pub replay_file: Option<String>,
+    pub enemies_file: Option<String>,
+    pub ai_scripts_file: Option<String>,
+    pub levels_file: Option<String>,
+    pub weapons_file: Option<String>,
+    //name of the weapon to start with, the first one in the weapons file when unset
+    pub weapon: Option<String>,
+    //grid or physics, replays always play in the mode they were recorded in
+    pub mode: GameMode,
+    //a held pad fires again every this many ms, unset fires once per press
+    pub pad_repeat_ms: Option<u64>,
+    //shoots on every step without the shoot pad, for setups that only have the left and right pads
+    pub auto_fire: bool,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            seed: None,
+            record_replays: true,
+            replay_dir: String::from("replays"),
+            replay_file: None,
+            enemies_file: None,
+            ai_scripts_file: None,
+            levels_file: None,
+            weapons_file: None,
+            weapon: None,
+            mode: GameMode::Grid,
+            pad_repeat_ms: None,
+            auto_fire: false,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn load() -> Self {
+        let path = env::var(GAME_CONFIG_PATH_ENV).unwrap_or_else(|_| GAME_CONFIG_PATH.to_string());
+        let mut config = Self::from_file(&path).unwrap_or_default();
+        config.apply_env();
+        config
+    }
+
+    fn from_file(path: &str) -> Option<Self> {
+        let contents = fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&contents) {
+            Ok(config) => Some(config),
+            Err(err) => {
+                eprintln!("Ignoring invalid game config {}: {}", path, err);
+                None
+            }
+        }
+    }
 
+    fn apply_env(&mut self) {
+        if let Ok(seed) = env::var(SEED_ENV) {
+            match seed.parse() {
+                Ok(seed) => self.seed = Some(seed),
+                Err(_) => eprintln!("Ignoring invalid {}: {}", SEED_ENV, seed),
+            }
+        }
+        if let Ok(replay_file) = env::var(REPLAY_FILE_ENV) {
+            self.replay_file = Some(replay_file);
+        }
+    }
+}
+
+//the gameplay side of the settings panel, the panel itself is drawn and clicked in SettingsPanel
 #[derive(Clone)]
 pub struct Settings {
     pub value_stats: Values,
-    settings_buttons: Buttons,
 }
 
 impl Settings {
     pub fn new() -> Self {
         Self {
             value_stats: Values::new(),
-            settings_buttons: Buttons::new(),
         }
     }
 
-    pub fn draw(&self, ctx: &mut Context, font: FontKey) {
-        self.value_stats.draw(ctx, font);
-        self.settings_buttons.draw(ctx, font);
-    }
-
-    pub fn handle_click(&mut self, x: u32, y: u32) -> Option<ButtonAction> {
-        if let Some(action) = self.settings_buttons.find_clicked_button(x, y) {
-            self.value_stats.handle_action(action)
-        } else {
-            None
-        }
+    pub fn apply_action(&mut self, action: ButtonAction) -> Option<ButtonAction> {
+        self.value_stats.handle_action(action)
     }
 }
 
@@ -39,6 +110,8 @@ pub struct Values {
     pub fly_speed: Duration,
     pub laser_speed: Duration,
     pub shooting_randomness: u32,
+    pub fly_count_multiplier: f32,
+    pub drop_percentage: f32,
 }
 
 impl Values {
@@ -51,6 +124,36 @@ impl Values {
             fly_speed: Duration::from_millis(900),
             laser_speed: Duration::from_millis(300),
             shooting_randomness: 10,
+            fly_count_multiplier: 1.0,
+            drop_percentage: 10.0,
+        }
+    }
+
+    //maps the server difficulty onto the gameplay values, 50% matches the defaults, above it enemies shoot more often
+    pub fn apply_difficulty(&mut self, config: &DifficultyConfig) {
+        let difficulty = config.difficulty_percentage.clamp(0.0, 100.0);
+
+        self.fly_speed = Duration::from_millis((1500.0 - difficulty * 12.0) as u64);
+        self.shooting_randomness = (10.0 - ((difficulty - 50.0) * 9.0 / 50.0).round()).clamp(1.0, 10.0) as u32;
+        self.fly_count_multiplier = 0.5 + difficulty / 100.0;
+        self.drop_percentage = config.drop_percentage.clamp(0.0, 100.0);
+
+        println!(
+            "Applied difficulty {}%: fly speed {} ms, shooting randomness {}, fly count x{:.2}, drops {}%",
+            difficulty,
+            self.fly_speed.as_millis(),
+            self.shooting_randomness,
+            self.fly_count_multiplier,
+            self.drop_percentage,
+        );
+    }
+
+    pub fn applied_difficulty(&self) -> AppliedDifficulty {
+        AppliedDifficulty {
+            fly_speed_ms: self.fly_speed.as_millis() as u64,
+            shooting_randomness: self.shooting_randomness,
+            fly_count_multiplier: self.fly_count_multiplier,
+            drop_percentage: self.drop_percentage,
         }
     }
 
@@ -91,6 +194,9 @@ impl Values {
             ButtonAction::ToggleLaserShooting => {
                 self.laser_shoot = !self.laser_shoot;
             }
+            ButtonAction::NextWeapon => {
+                return Some(ButtonAction::NextWeapon);
+            }
             ButtonAction::Reset => {
                 return Some(ButtonAction::Reset);
             }
@@ -98,35 +204,10 @@ impl Values {
 
         Some(action)
     }
-
-    fn draw(&self, ctx: &mut Context, font: FontKey) {
-        self.draw_stats_text(ctx, 30, 780, format!("Fly Speed: {} ms", self.fly_speed.as_millis()), font);
-        self.draw_stats_text(ctx, 310, 780, format!("Laser Speed: {} ms", self.laser_speed.as_millis()), font);
-        self.draw_stats_text(ctx, 570, 780, format!("Flies: {}", self.number_of_flies), font);
-        self.draw_stats_text(ctx, 570, 810, format!("Invincible: {}", if self.invincible { "ON" } else { "OFF" }), font);
-        self.draw_stats_text(ctx, 30, 810, format!("Fly Movement: {}", if self.fly_move { "ON" } else { "OFF" }), font);
-        self.draw_stats_text(ctx, 310, 810, format!("Laser Shoot: {}", if self.laser_shoot { "ON" } else { "OFF" }), font);
-    }
-
-    fn draw_stats_text(&self, ctx: &mut Context, x: u32, y: u32, content: String, font: FontKey) {
-        ctx.draw(
-            CanvasItem::Text(
-                Area((x, y), None),
-                Text::new(
-                    content.leak(),
-                    "FFFFFF",
-                    255,
-                    Some(800),
-                    20,
-                    25,
-                    font
-                )
-            )
-        );
-    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ButtonAction {
     FlySpeedDecrease,
     FlySpeedIncrease,
@@ -137,99 +218,6 @@ pub enum ButtonAction {
     ToggleInvincible,
     ToggleFlyMovement,
     ToggleLaserShooting,
+    NextWeapon,
     Reset,
 }
-
-#[derive(Clone)]
-struct Button {
-    pub action: ButtonAction,
-    pub size: (u32, u32),
-    pub offset: (u32, u32),
-    pub text: &'static str,
-}
-
-impl Button {
-    fn new(action: ButtonAction, size: (u32, u32), offset: (u32, u32), text: &'static str) -> Button {
-        Button {
-            action,
-            size,
-            offset,
-            text,
-        }
-    }
-
-    fn is_within_bounds(&self, x: u32, y: u32) -> bool {
-        x >= self.offset.0 && x <= self.offset.0 + self.size.0 &&
-            y >= self.offset.1 && y <= self.offset.1 + self.size.1
-    }
-}
-
-#[derive(Clone)]
-struct Buttons {
-    buttons: Vec<Button>,
-}
-
-impl Buttons {
-    fn new() -> Self {
-        Self {
-            buttons: vec![
-                Button::new(ButtonAction::FlySpeedIncrease, (130, 40), (30, 910), "Fly Speed +"),
-                Button::new(ButtonAction::FlySpeedDecrease, (130, 40), (30, 850), "Fly Speed -"),
-                Button::new(ButtonAction::LaserSpeedIncrease, (165, 40), (190, 910), "Laser Speed +"),
-                Button::new(ButtonAction::LaserSpeedDecrease, (165, 40), (190, 850), "Laser Speed -"),
-                Button::new(ButtonAction::FliesDecrease, (73, 40), (380, 910), "Flies -"),
-                Button::new(ButtonAction::FliesIncrease, (73, 40), (380, 850), "Flies +"),
-                Button::new(ButtonAction::ToggleInvincible, (105, 40), (480, 910), "Invincible"),
-                Button::new(ButtonAction::ToggleFlyMovement, (100, 40), (480, 850), "Fly Move"),
-                Button::new(ButtonAction::ToggleLaserShooting, (75, 40), (615, 910), "Lasers"),
-                Button::new(ButtonAction::Reset, (170, 40), (860, 880), "Save & Restart"),
-            ]
-        }
-    }
-
-    fn draw(&self, ctx: &mut Context, font: FontKey) {
-        for button in &self.buttons {
-            self.draw_button(ctx, button, font);
-        }
-    }
-
-    fn draw_button(&self, ctx: &mut Context, button: &Button, font: FontKey) {
-        let text_struct = Text::new(button.text, "FFFFFF", 255, Some(800), 25, 38, font);
-        let text_size = ctx.messure_text(&text_struct);
-
-        let text_x = match text_size.0 < button.size.0 {
-            true => button.offset.0 + (button.size.0 - text_size.0) / 2,
-            false => button.offset.0,
-        };
-
-        let text_y = match text_size.1 < button.size.1 {
-            true => button.offset.1 + (button.size.1 - text_size.1) / 2,
-            false => button.offset.1,
-        };
-
-        ctx.draw(
-            CanvasItem::Shape(
-                Area(button.offset, None),
-                Shape::RoundedRectangle(0, (button.size.0, 48), 5),
-                "FF4500",
-                255,
-            )
-        );
-
-        ctx.draw(
-            CanvasItem::Text(
-                Area((text_x, text_y), None),
-                text_struct
-            )
-        );
-    }
-
-    fn find_clicked_button(&self, x: u32, y: u32) -> Option<ButtonAction> {
-        for button in &self.buttons {
-            if button.is_within_bounds(x, y) {
-                return Some(button.action.clone());
-            }
-        }
-        None
-    }
-}
@@ -0,0 +1,133 @@
+use rust_on_rails::prelude::*;
+use rust_on_rails::canvas::{Area, CanvasItem};
+use crate::layout::{Layout, PANEL_HEIGHT, PANEL_PADDING, PANEL_WIDTH, PANEL_X, PANEL_Y};
+use crate::settings::{ButtonAction, Values};
+
+#[derive(Clone)]
+struct Button {
+    pub action: ButtonAction,
+    pub size: (u32, u32),
+    pub offset: (u32, u32),
+    pub text: &'static str,
+}
+
+impl Button {
+    fn new(action: ButtonAction, size: (u32, u32), offset: (u32, u32), text: &'static str) -> Button {
+        Button {
+            action,
+            size,
+            offset,
+            text,
+        }
+    }
+
+    fn is_within_bounds(&self, x: u32, y: u32) -> bool {
+        x >= self.offset.0 && x <= self.offset.0 + self.size.0 &&
+            y >= self.offset.1 && y <= self.offset.1 + self.size.1
+    }
+}
+
+//the settings panel under the board, the values it shows and the buttons that change them
+pub struct SettingsPanel {
+    buttons: Vec<Button>,
+}
+
+//the top of the two rows of buttons in the settings panel, a column with one button sits between them
+const BUTTON_ROWS: [u32; 2] = [PANEL_Y + 90, PANEL_Y + 150];
+const BUTTON_HEIGHT: u32 = 40;
+
+impl SettingsPanel {
+    //the columns are spread evenly across the settings panel, each as wide as its widest button
+    pub fn new() -> Self {
+        let columns = vec![
+            (130, vec![(ButtonAction::FlySpeedDecrease, "Fly Speed -"), (ButtonAction::FlySpeedIncrease, "Fly Speed +")]),
+            (165, vec![(ButtonAction::LaserSpeedDecrease, "Laser Speed -"), (ButtonAction::LaserSpeedIncrease, "Laser Speed +")]),
+            (73, vec![(ButtonAction::FliesIncrease, "Flies +"), (ButtonAction::FliesDecrease, "Flies -")]),
+            (105, vec![(ButtonAction::ToggleFlyMovement, "Fly Move"), (ButtonAction::ToggleInvincible, "Invincible")]),
+            (95, vec![(ButtonAction::NextWeapon, "Weapon"), (ButtonAction::ToggleLaserShooting, "Lasers")]),
+            (170, vec![(ButtonAction::Reset, "Save & Restart")]),
+        ];
+
+        let inner_width = PANEL_WIDTH - 2 * PANEL_PADDING;
+        let buttons_width: u32 = columns.iter().map(|(width, _)| width).sum();
+        let gap = inner_width.saturating_sub(buttons_width) / (columns.len() as u32 - 1);
+
+        let single_y = (BUTTON_ROWS[0] + BUTTON_ROWS[1]) / 2;
+        let mut buttons = Vec::new();
+        let mut x = PANEL_X + PANEL_PADDING;
+        for (width, column) in columns {
+            let single = column.len() == 1;
+            for (row, (action, text)) in column.into_iter().enumerate() {
+                let y = if single { single_y } else { BUTTON_ROWS[row] };
+                buttons.push(Button::new(action, (width, BUTTON_HEIGHT), (x, y), text));
+            }
+            x += width + gap;
+        }
+
+        Self { buttons }
+    }
+
+    pub fn draw(&self, ctx: &mut Context, layout: &Layout, values: &Values, font: FontKey) {
+        ctx.draw(layout.rounded_rectangle((PANEL_X, PANEL_Y), (PANEL_WIDTH, PANEL_HEIGHT), 10, "0D1F2D", 255));
+        self.draw_values(ctx, layout, values, font);
+        for button in &self.buttons {
+            self.draw_button(ctx, layout, button, font);
+        }
+    }
+
+    //three columns of two lines across the top of the settings panel
+    fn draw_values(&self, ctx: &mut Context, layout: &Layout, values: &Values, font: FontKey) {
+        let lines = [
+            format!("Fly Speed: {} ms", values.fly_speed.as_millis()),
+            format!("Fly Movement: {}", if values.fly_move { "ON" } else { "OFF" }),
+            format!("Laser Speed: {} ms", values.laser_speed.as_millis()),
+            format!("Laser Shoot: {}", if values.laser_shoot { "ON" } else { "OFF" }),
+            format!("Flies: {}", values.number_of_flies),
+            format!("Invincible: {}", if values.invincible { "ON" } else { "OFF" }),
+        ];
+
+        let column_width = (PANEL_WIDTH - 2 * PANEL_PADDING) / 3;
+        for (index, content) in lines.into_iter().enumerate() {
+            let x = PANEL_X + PANEL_PADDING + (index as u32 / 2) * column_width;
+            let y = PANEL_Y + 20 + (index as u32 % 2) * 30;
+            ctx.draw(layout.text_at((x, y), layout.text(content.leak(), "FFFFFF", column_width, 20, 25, font)));
+        }
+    }
+
+    fn draw_button(&self, ctx: &mut Context, layout: &Layout, button: &Button, font: FontKey) {
+        let text_struct = layout.text(button.text, "FFFFFF", 800, 25, 38, font);
+        // the text is measured in window pixels, so it is centred in the scaled button
+        let text_size = ctx.messure_text(&text_struct);
+        let offset = layout.point(button.offset);
+        let size = layout.size(button.size);
+
+        let text_x = match text_size.0 < size.0 {
+            true => offset.0 + (size.0 - text_size.0) / 2,
+            false => offset.0,
+        };
+
+        let text_y = match text_size.1 < size.1 {
+            true => offset.1 + (size.1 - text_size.1) / 2,
+            false => offset.1,
+        };
+
+        ctx.draw(layout.rounded_rectangle(button.offset, button.size, 5, "FF4500", 255));
+
+        ctx.draw(
+            CanvasItem::Text(
+                Area((text_x, text_y), None),
+                text_struct
+            )
+        );
+    }
+
+    //`x` and `y` are in design units, see Layout::to_design
+    pub fn find_clicked_button(&self, x: u32, y: u32) -> Option<ButtonAction> {
+        for button in &self.buttons {
+            if button.is_within_bounds(x, y) {
+                return Some(button.action.clone());
+            }
+        }
+        None
+    }
+}
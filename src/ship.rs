@@ -1,170 +1,185 @@
+use crate::collision::{projectile_collision, Collision};
+use crate::enemy::EnemyId;
+use crate::entrance::ENTRANCE_SCORE_MULTIPLIER;
+use crate::formation::Formation;
+use crate::physics::PhysicsWorld;
+use crate::power_up::PowerUpKind;
 use crate::settings::Settings;
-use crate::ship_ai::{AIAction, ShipAI};
-use crate::structs::{Cords, RelCords, ShipAction, ROWS};
-use std::collections::HashMap;
+use crate::ship_ai::{AIAction, AIContext, ShipAI};
+use crate::structs::{Cords, RelCords, ShipAction, COLUMNS, ROWS};
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
 use uuid::Uuid;
 
-pub trait Ship {
-    fn display_type(&self) -> &str;
-    fn get_id(&self) -> Uuid;
-    fn get_action(&mut self, cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>, settings: &Settings) -> ShipAction;
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ShipKind {
+    Enemy(EnemyId),
+    Explosion,
+    Projectile(ProjectileKind),
+    TractorBeam,
+    PowerUp(PowerUpKind),
 }
 
-pub struct FlyShip {
-    ai: ShipAI,
-    id: Uuid,
+//the shapes of shot, each one gets its own sprite
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectileKind {
+    #[default]
+    Bullet,
+    Spread,
 }
 
-impl FlyShip {
-    pub fn new() -> Self {
-        Self {
-            ai: ShipAI::new(
-                vec![
-                    //   AIAction::new_await(AIAction::MoveCautious(RelCords(1, 1)), |s: &Settings| s.value_stats.fly_speed),
-                    AIAction::RandomShoot,
-                    //  AIAction::new_await(AIAction::MoveCautious(RelCords(-1, 1)), |s: &Settings| s.value_stats.fly_speed),
-                    AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                    AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                    AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                    AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                    AIAction::RandomShoot,
-                ]
-            ),
-            id: Uuid::new_v4(),
+impl ProjectileKind {
+    pub const ALL: [ProjectileKind; 2] = [ProjectileKind::Bullet, ProjectileKind::Spread];
+}
+
+//which way a projectile flies across the board
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Heading {
+    Up,
+    Down,
+}
+
+impl ShipKind {
+    pub fn is_enemy(self) -> bool {
+        match self {
+            ShipKind::Enemy(_) => true,
+            ShipKind::Explosion | ShipKind::Projectile(_) | ShipKind::TractorBeam | ShipKind::PowerUp(_) => false,
         }
     }
-}
 
-impl Ship for FlyShip {
-    fn display_type(&self) -> &str {
-        "fly"
+    pub fn is_projectile(self) -> bool {
+        match self {
+            ShipKind::Projectile(_) => true,
+            ShipKind::Enemy(_) | ShipKind::Explosion | ShipKind::TractorBeam | ShipKind::PowerUp(_) => false,
+        }
     }
 
-    fn get_id(&self) -> Uuid {
-        self.id
+    pub fn is_effect(self) -> bool {
+        match self {
+            ShipKind::Explosion | ShipKind::TractorBeam => true,
+            ShipKind::Enemy(_) | ShipKind::Projectile(_) | ShipKind::PowerUp(_) => false,
+        }
     }
 
-    fn get_action(&mut self, cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>, settings: &Settings) -> ShipAction {
-        self.ai.get_action(cords, game_board, settings)
+    pub fn is_pickup(self) -> bool {
+        matches!(self, ShipKind::PowerUp(_))
     }
 }
 
-pub struct TikiFlyShip {
-    ai: ShipAI,
-    id: Uuid,
+//the side a ship fights on, bullets take the side of whoever fired them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Faction {
+    Player,
+    Enemy,
+    //explosions and drops, nobody's
+    Neutral,
 }
 
-impl TikiFlyShip {
-    pub fn new() -> Self {
-        Self {
-            ai: ShipAI::new(
-                vec![
-                    //   AIAction::new_await(AIAction::MoveCautious(RelCords(1, 1)), |s: &Settings| s.value_stats.fly_speed),
-                    AIAction::RandomShoot,
-                    //  AIAction::new_await(AIAction::MoveCautious(RelCords(-1, 1)), |s: &Settings| s.value_stats.fly_speed),
-                    AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                    AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                    AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                    AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                    AIAction::RandomShoot,
-                ]
-            ),
-            id: Uuid::new_v4(),
-        }
-    }
+//things that happened on the grid during a step that the rest of the game has to react to
+#[derive(Clone, Debug, PartialEq)]
+pub enum GridEvent {
+    FighterRescued,
+    //a hit the ship survived
+    ShipHit(Uuid),
+    EnemyDestroyed(Cords),
+    //a ship went from one cell to another, the renderer glides it across over `interval`, how long the move took
+    ShipMoved { id: Uuid, kind: ShipKind, from: Cords, to: Cords, interval: Duration },
+    //a free flying projectile ran into the fighter cell, only happens in physics mode
+    FighterStruck(Cords),
+    //the body of the ship in `ship` overlaps the fighter cell, only happens in physics mode
+    FighterTouched { ship: Cords, fighter: Cords },
 }
 
-impl Ship for TikiFlyShip {
-    fn display_type(&self) -> &str {
-        "tiki_fly"
+const TRACTOR_BEAM_DURATION: Duration = Duration::from_millis(2500);
+
+pub trait Ship {
+    fn kind(&self) -> ShipKind;
+
+    fn is_enemy(&self) -> bool {
+        self.kind().is_enemy()
     }
 
-    fn get_id(&self) -> Uuid {
-        self.id
+    fn is_projectile(&self) -> bool {
+        self.kind().is_projectile()
     }
 
-    fn get_action(&mut self, cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>, settings: &Settings) -> ShipAction {
-        self.ai.get_action(cords, game_board, settings)
+    fn is_effect(&self) -> bool {
+        self.kind().is_effect()
     }
-}
 
-pub struct NorthropFlyShip {
-    ai: ShipAI,
-    id: Uuid,
-}
+    //points for destroying this ship
+    fn score_value(&self) -> u32 {
+        0
+    }
 
-impl NorthropFlyShip {
-    pub fn new() -> Self {
-        Self {
-            ai: ShipAI::new(
-                vec![
-                 //   AIAction::new_await(AIAction::MoveCautious(RelCords(1, 1)), |s: &Settings| s.value_stats.fly_speed),
-                 AIAction::RandomShoot,
-                  //  AIAction::new_await(AIAction::MoveCautious(RelCords(-1, 1)), |s: &Settings| s.value_stats.fly_speed),
-                 AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                 AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                 AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                 AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                 AIAction::RandomShoot,
-                ]
-            ),
-            id: Uuid::new_v4(),
-        }
+    //hits left before the ship is destroyed
+    fn hit_points(&self) -> u32 {
+        1
     }
-}
 
-impl Ship for NorthropFlyShip {
-    fn display_type(&self) -> &str {
-        "northrop_fly"
+    //the formation slot the ship belongs to, None for ships that aren't part of the formation
+    fn anchor(&self) -> Option<Cords> {
+        None
     }
 
-    fn get_id(&self) -> Uuid {
-        self.id
+    fn set_anchor(&mut self, _anchor: Cords) {}
+
+    //an ai to fly once before the ship's own one takes over
+    fn set_entry(&mut self, _entry: ShipAI) {}
+
+    //still flying its entry, before it has settled into the formation
+    fn is_entering(&self) -> bool {
+        false
     }
 
-    fn get_action(&mut self, cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>, settings: &Settings) -> ShipAction {
-        self.ai.get_action(cords, game_board, settings)
+    //returns true when the hit destroys the ship
+    fn take_hit(&mut self) -> bool {
+        true
     }
-}
 
-pub struct B2FlyShip {
-    ai: ShipAI,
-    id: Uuid,
-}
+    fn is_damaged(&self) -> bool {
+        false
+    }
 
-impl B2FlyShip {
-    pub fn new() -> Self {
-        Self {
-            ai: ShipAI::new(
-                vec![
-                    //   AIAction::new_await(AIAction::MoveCautious(RelCords(1, 1)), |s: &Settings| s.value_stats.fly_speed),
-                    AIAction::RandomShoot,
-                    //  AIAction::new_await(AIAction::MoveCautious(RelCords(-1, 1)), |s: &Settings| s.value_stats.fly_speed),
-                    AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                    AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                    AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                    AIAction::new_await(AIAction::Nothing,|s: &Settings| s.value_stats.fly_speed),
-                    AIAction::RandomShoot,
-                ]
-            ),
-            id: Uuid::new_v4(),
+    //bosses carry the player's fighter away after a tractor beam catches it
+    fn holds_captive(&self) -> bool {
+        false
+    }
+
+    fn capture_fighter(&mut self) {}
+
+    //the ship that put this one on the board
+    fn owner(&self) -> Option<Uuid> {
+        None
+    }
+
+    fn faction(&self) -> Faction {
+        match self.kind() {
+            ShipKind::Enemy(_) | ShipKind::TractorBeam => Faction::Enemy,
+            ShipKind::Projectile(_) | ShipKind::Explosion | ShipKind::PowerUp(_) => Faction::Neutral,
         }
     }
-}
 
-impl Ship for B2FlyShip {
-    fn display_type(&self) -> &str {
-        "b2_fly"
+    //the way a projectile flies, None for everything that isn't one
+    fn heading(&self) -> Option<Heading> {
+        None
     }
 
-    fn get_id(&self) -> Uuid {
-        self.id
+    //how long the ship takes to move one cell at normal speed, None falls back to the settings
+    fn move_interval(&self) -> Option<Duration> {
+        None
     }
 
-    fn get_action(&mut self, cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>, settings: &Settings) -> ShipAction {
-        self.ai.get_action(cords, game_board, settings)
+    //bullets the player fired, they count toward the weapon's limit on the screen
+    fn is_player_bullet(&self) -> bool {
+        self.is_projectile() && self.faction() == Faction::Player
     }
+
+    fn get_id(&self) -> Uuid;
+    fn get_action(&mut self, cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>, ctx: &AIContext, rng: &mut StdRng) -> ShipAction;
 }
 
 pub struct ExplosionShip {
@@ -184,80 +199,150 @@ impl ExplosionShip {
 }
 
 impl Ship for ExplosionShip {
-    fn display_type(&self) -> &str {
-        "explosion"
+    fn kind(&self) -> ShipKind {
+        ShipKind::Explosion
     }
 
     fn get_id(&self) -> Uuid {
         self.id
     }
 
-    fn get_action(&mut self, cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>, settings: &Settings) -> ShipAction {
-        self.ai.get_action(cords, game_board, settings)
+    fn get_action(&mut self, cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>, ctx: &AIContext, rng: &mut StdRng) -> ShipAction {
+        self.ai.get_action(cords, game_board, ctx, rng)
     }
 }
 
 pub struct BulletShip {
     ai: ShipAI,
     id: Uuid,
-    moving_down: bool,
+    projectile: ProjectileKind,
+    faction: Faction,
+    heading: Heading,
+    owner: Option<Uuid>,
+    speed: Option<Duration>,
 }
 
 impl BulletShip {
-    pub fn new(moving_down: bool) -> Self {
-        let movement = if moving_down { (1, 0) } else { (-1, 0) };
+    //player bullets fly up and enemy bullets down, one row every `speed` or the laser speed setting when None
+    pub fn new(projectile: ProjectileKind, faction: Faction, owner: Option<Uuid>, speed: Option<Duration>) -> Self {
+        let heading = if faction == Faction::Player { Heading::Up } else { Heading::Down };
+        let movement = match heading {
+            Heading::Up => RelCords(-1, 0),
+            Heading::Down => RelCords(1, 0),
+        };
 
         Self {
             ai: ShipAI::new(
-                vec![AIAction::new_await(AIAction::RelativeMove(RelCords(movement.0, movement.1)), |s: &Settings| s.value_stats.laser_speed)]
+                vec![AIAction::new_await(AIAction::RelativeMove(movement), move |s: &Settings| speed.unwrap_or(s.value_stats.laser_speed))]
             ),
             id: Uuid::new_v4(),
-            moving_down,
+            projectile,
+            faction,
+            heading,
+            owner,
+            speed,
         }
     }
 }
 
 impl Ship for BulletShip {
-    fn display_type(&self) -> &str {
-        "bullet"
+    fn kind(&self) -> ShipKind {
+        ShipKind::Projectile(self.projectile)
+    }
+
+    fn faction(&self) -> Faction {
+        self.faction
+    }
+
+    fn heading(&self) -> Option<Heading> {
+        Some(self.heading)
+    }
+
+    fn move_interval(&self) -> Option<Duration> {
+        self.speed
+    }
+
+    fn owner(&self) -> Option<Uuid> {
+        self.owner
     }
 
     fn get_id(&self) -> Uuid {
         self.id
     }
 
-    fn get_action(&mut self, cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>, settings: &Settings) -> ShipAction {
-        self.ai.get_action(cords, game_board, settings)
+    fn get_action(&mut self, cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>, ctx: &AIContext, rng: &mut StdRng) -> ShipAction {
+        self.ai.get_action(cords, game_board, ctx, rng)
     }
 }
 
-pub fn new_fly_ship() -> Box<dyn Ship> {
-    Box::new(FlyShip::new())
+pub struct TractorBeamShip {
+    ai: ShipAI,
+    id: Uuid,
+    owner: Uuid,
 }
 
-pub fn new_tiki_fly_ship() -> Box<dyn Ship> {
-    Box::new(TikiFlyShip::new())
+impl TractorBeamShip {
+    pub fn new(owner: Uuid) -> Self {
+        Self {
+            ai: ShipAI::new(
+                vec![AIAction::new_await(AIAction::Remove, |_: &Settings| TRACTOR_BEAM_DURATION)]
+            ),
+            id: Uuid::new_v4(),
+            owner,
+        }
+    }
 }
 
-pub fn new_northrop_fly_ship() -> Box<dyn Ship> {
-    Box::new(NorthropFlyShip::new())
-}
+impl Ship for TractorBeamShip {
+    fn kind(&self) -> ShipKind {
+        ShipKind::TractorBeam
+    }
+
+    fn owner(&self) -> Option<Uuid> {
+        Some(self.owner)
+    }
 
-pub fn new_b2_fly_ship() -> Box<dyn Ship> {
-    Box::new(B2FlyShip::new())
+    fn get_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn get_action(&mut self, cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>, ctx: &AIContext, rng: &mut StdRng) -> ShipAction {
+        self.ai.get_action(cords, game_board, ctx, rng)
+    }
 }
 
 pub fn new_explosion_ship() -> Box<dyn Ship> {
     Box::new(ExplosionShip::new())
 }
 
-pub fn new_bullet_ship(moving_down: bool) -> Box<dyn Ship> {
-    Box::new(BulletShip::new(moving_down))
+pub fn new_enemy_bullet(owner: Uuid) -> Box<dyn Ship> {
+    Box::new(BulletShip::new(ProjectileKind::Bullet, Faction::Enemy, Some(owner), None))
+}
+
+pub fn new_player_bullet(projectile: ProjectileKind, speed: Option<Duration>) -> Box<dyn Ship> {
+    Box::new(BulletShip::new(projectile, Faction::Player, None, speed))
+}
+
+//how long the ship's next move takes in game time, waits run out `ctx.speed` times as fast on later levels
+fn move_duration(ship: &dyn Ship, ctx: &AIContext) -> Duration {
+    let values = &ctx.settings.value_stats;
+    let interval = ship.move_interval().unwrap_or(if ship.is_projectile() { values.laser_speed } else { values.fly_speed });
+    if ctx.speed > 0.0 {
+        interval.div_f64(ctx.speed as f64)
+    } else {
+        interval
+    }
 }
 
 pub struct ShipGrid {
     pub grid: HashMap<Cords, Box<dyn Ship>>,
     pub score: u32,
+    //kills are worth this many times their points, raised while the score multiplier power-up is running
+    pub score_multiplier: u32,
+    pub formation: Formation,
+    pub events: Vec<GridEvent>,
+    //the continuous positions of everything on the board, None in grid mode
+    pub physics: Option<PhysicsWorld>,
 }
 
 impl ShipGrid {
@@ -265,28 +350,52 @@ impl ShipGrid {
         ShipGrid {
             grid: HashMap::new(),
             score: 0,
+            score_multiplier: 1,
+            formation: Formation::new([]),
+            events: Vec::new(),
+            physics: None,
         }
     }
 
+    pub fn with_physics() -> Self {
+        ShipGrid {
+            physics: Some(PhysicsWorld::new()),
+            ..Self::new()
+        }
+    }
+
+    //replaces the board with a freshly spawned wave and lines the formation up with its anchors
+    pub fn set_wave(&mut self, grid: HashMap<Cords, Box<dyn Ship>>) {
+        self.formation = Formation::new(grid.values().filter_map(|ship| ship.anchor()));
+        self.grid = grid;
+        self.clear_physics();
+    }
+
+    //an empty board with the formation laid out for ships that are still going to fly in
+    pub fn set_incoming_wave(&mut self, wave: &HashMap<Cords, Box<dyn Ship>>) {
+        self.formation = Formation::new(wave.values().filter_map(|ship| ship.anchor()));
+        self.grid = HashMap::new();
+        self.clear_physics();
+    }
+
     pub fn move_entity(
         &mut self,
         old_coords: Cords,
         new_coords: Cords,
         wrapped: bool,
-    ) -> Result<Option<String>, &'static str> {
+        interval: Duration,
+    ) -> Result<Option<ShipKind>, &'static str> {
         if wrapped {
-            if let Some(mut entity) = self.grid.remove(&old_coords) {
-                if entity.display_type() == "bullet" {
+            if let Some(entity) = self.grid.remove(&old_coords) {
+                // bullets and drops are gone once they leave the board
+                if entity.is_projectile() || entity.kind().is_pickup() {
                     return Ok(None);
-                } else if entity.display_type() == "fly"
-                    || entity.display_type() == "tiki_fly"
-                    || entity.display_type() == "northrop_fly"
-                    || entity.display_type() == "b2_fly" {
-                    return if old_coords.0 < new_coords.0 && old_coords.0 <ROWS / 2 && new_coords.0 > ROWS / 2 {
+                } else if entity.is_enemy() {
+                    return if (old_coords.0 < new_coords.0 && old_coords.0 <ROWS / 2 && new_coords.0 > ROWS / 2) || self.grid.contains_key(&new_coords) {
                         self.grid.insert(old_coords, entity);
                         Ok(None)
                     } else {
-                        self.grid.insert(new_coords, entity);
+                        self.place_moved(old_coords, new_coords, entity, interval);
                         Ok(None)
                     }
                 } else {
@@ -297,7 +406,7 @@ impl ShipGrid {
 
         if new_coords.0 >= ROWS {
             if let Some(entity) = self.grid.remove(&old_coords) {
-                if entity.display_type() == "bullet" {
+                if entity.is_projectile() {
                     return Ok(None);
                 } else {
                     self.grid.insert(old_coords, entity);
@@ -305,96 +414,217 @@ impl ShipGrid {
             }
         }
 
-        if let Some(entity) = self.grid.remove(&old_coords) {
-            let is_bullet = entity.display_type() == "bullet";
-
-            if self.grid.contains_key(&new_coords) {
-                if is_bullet {
-                    let existing_ship = self.grid.remove(&new_coords);
-                    let removed_type = existing_ship.as_ref().map(|ship| ship.display_type().to_string());
+        let Some(entity) = self.grid.remove(&old_coords) else {
+            return Err("no entity found at old cords");
+        };
 
-                    self.grid.insert(new_coords, new_explosion_ship());
+        let Some(target) = self.grid.get(&new_coords) else {
+            self.place_moved(old_coords, new_coords, entity, interval);
+            return Ok(None);
+        };
 
-                    if let Some(removed_type_str) = &removed_type {
-                        match removed_type_str.as_str() {
-                            "fly" => self.score += 100,
-                            "tiki_fly" => self.score += 150,
-                            "northrop_fly" => self.score += 200,
-                            "b2_fly" => self.score += 300,
-                            _ => {}
-                        }
-                    }
+        if !entity.is_projectile() {
+            self.grid.insert(old_coords, entity);
+            return Err("target pos in use");
+        }
 
-                    Ok(removed_type)
-                } else {
-                    self.grid.insert(old_coords, entity);
-                    Err("target pos in use")
+        match projectile_collision(entity.as_ref(), target.as_ref()) {
+            Collision::Hit => Ok(self.hit_ship(entity.as_ref(), new_coords)),
+            Collision::Absorb => Ok(None),
+            Collision::Cancel => {
+                self.grid.remove(&new_coords);
+                Ok(None)
+            }
+            Collision::Pass => {
+                // carries on to the cell past the target, a bullet with nowhere to land is used up
+                let past = (2 * new_coords.0 as isize - old_coords.0 as isize, 2 * new_coords.1 as isize - old_coords.1 as isize);
+                let on_board = past.0 >= 0 && past.0 < ROWS as isize && past.1 >= 0 && past.1 < COLUMNS as isize;
+                let past = Cords(past.0 as usize, past.1 as usize);
+                if on_board && !self.grid.contains_key(&past) {
+                    self.place_moved(old_coords, past, entity, interval);
                 }
-            } else {
-                self.grid.insert(new_coords, entity);
                 Ok(None)
             }
-        } else {
-            Err("no entity found at old cords")
+            Collision::Block => {
+                self.grid.insert(old_coords, entity);
+                Err("target pos in use")
+            }
+        }
+    }
+
+    fn place_moved(&mut self, from: Cords, to: Cords, ship: Box<dyn Ship>, interval: Duration) {
+        self.events.push(GridEvent::ShipMoved { id: ship.get_id(), kind: ship.kind(), from, to, interval });
+        self.grid.insert(to, ship);
+    }
+
+    //the bullet is used up either way, the ship only goes when its last hit point does
+    pub(crate) fn hit_ship(&mut self, bullet: &dyn Ship, coords: Cords) -> Option<ShipKind> {
+        let destroyed = self.grid.get_mut(&coords).is_none_or(|ship| ship.take_hit());
+        if !destroyed {
+            if let Some(ship) = self.grid.get(&coords) {
+                self.events.push(GridEvent::ShipHit(ship.get_id()));
+            }
+            return None;
+        }
+
+        let ship = self.grid.remove(&coords)?;
+        self.grid.insert(coords, new_explosion_ship());
+
+        // only the player's own kills are worth points
+        if bullet.faction() == Faction::Player {
+            let points = if ship.is_entering() {
+                ship.score_value() * ENTRANCE_SCORE_MULTIPLIER
+            } else {
+                ship.score_value()
+            };
+            self.score += points * self.score_multiplier;
         }
+        if ship.holds_captive() {
+            self.events.push(GridEvent::FighterRescued);
+        }
+        if ship.is_enemy() {
+            self.events.push(GridEvent::EnemyDestroyed(coords));
+        }
+
+        Some(ship.kind())
     }
 
-    pub fn process_ship_actions(&mut self, settings: &Settings) {
-        let entries: Vec<(Cords, Uuid)> = self.grid
+    pub fn process_ship_actions(&mut self, ctx: &AIContext, rng: &mut StdRng) {
+        if ctx.settings.value_stats.fly_move {
+            self.step_formation(ctx);
+        }
+
+        let mut entries: Vec<(Cords, Uuid)> = self.grid
             .iter()
             .map(|(&coords, ship)| (coords, ship.get_id()))
             .collect();
+        // HashMap order changes between runs, sorting keeps the rng rolls reproducible
+        entries.sort_by_key(|(coords, _)| *coords);
 
-        let mut actions_to_preform: Vec<(Cords, ShipAction)> = Vec::new();
+        let mut actions_to_preform: Vec<(Cords, ShipAction, Duration)> = Vec::new();
 
         for (coords, _) in &entries {
             if let Some(mut ship) = self.grid.remove(coords) {
-                let action = ship.get_action(*coords, &self.grid, settings);
+                let ship_ctx = AIContext {
+                    hit_points: ship.hit_points(),
+                    home: ship.anchor().map(|anchor| self.formation.slot(anchor)),
+                    ..*ctx
+                };
+                let action = ship.get_action(*coords, &self.grid, &ship_ctx, rng);
+                let interval = move_duration(ship.as_ref(), ctx);
 
                 self.grid.insert(*coords, ship);
 
-                actions_to_preform.push((*coords, action));
+                actions_to_preform.push((*coords, action, interval));
             }
         }
 
         self.execute_actions(&actions_to_preform);
     }
 
-    fn execute_actions(&mut self, actions: &[(Cords, ShipAction)]) {
-        for (coords, action) in actions {
+    //moves every ship sitting in its slot to its next slot together, ships away on a dive head for the new slot on their own
+    fn step_formation(&mut self, ctx: &AIContext) {
+        if !self.formation.tick(ctx.dt, ctx.settings.value_stats.fly_speed) {
+            return;
+        }
+
+        let moving: Vec<(Cords, Cords)> = self.grid
+            .iter()
+            .filter_map(|(&coords, ship)| {
+                let anchor = ship.anchor()?;
+                (self.formation.slot(anchor) == coords).then(|| (coords, self.formation.next_slot(anchor)))
+            })
+            .collect();
+
+        // wait for the next tick if anything outside the formation is in the way
+        let sources: HashSet<Cords> = moving.iter().map(|(from, _)| *from).collect();
+        if moving.iter().any(|(_, to)| self.grid.contains_key(to) && !sources.contains(to)) {
+            return;
+        }
+
+        let ships: Vec<(Cords, Cords, Box<dyn Ship>)> = moving
+            .iter()
+            .filter_map(|(from, to)| self.grid.remove(from).map(|ship| (*from, *to, ship)))
+            .collect();
+        for (from, to, ship) in ships {
+            self.place_moved(from, to, ship, ctx.settings.value_stats.fly_speed);
+        }
+
+        self.formation.advance();
+    }
+
+    fn execute_actions(&mut self, actions: &[(Cords, ShipAction, Duration)]) {
+        for (coords, action, interval) in actions {
             if !self.grid.contains_key(coords) {
                 continue;
             }
 
             match action {
                 ShipAction::Move(new_coords, wrapped) => {
-                    let result = self.move_entity(*coords, *new_coords, *wrapped);
-
-                    if let Ok(Some(removed_type)) = result {
-                    }
+                    // a blocked move just leaves the ship where it is
+                    let _ = self.move_entity(*coords, *new_coords, *wrapped, *interval);
                 },
                 ShipAction::Shoot => {
                     let bullet_coords = Cords(coords.0 + 1, coords.1);
-                    self.grid.insert(bullet_coords, new_bullet_ship(true));
+                    let owner = self.grid.get(coords).map(|ship| ship.get_id());
+                    if let (Some(owner), true) = (owner, bullet_coords.0 < ROWS) {
+                        self.grid.entry(bullet_coords).or_insert_with(|| new_enemy_bullet(owner));
+                    }
                 },
+                // leaving the board on its own isn't a kill, so no points
                 ShipAction::Remove => {
-                    if let Some(ship) = self.grid.get(coords) {
-                        match ship.display_type() {
-                            "fly" => self.score += 100,
-                            "tiki_fly" => self.score += 150,
-                            "northrop_fly" => self.score += 200,
-                            "b2_fly" => self.score += 300,
-                            _ => {}
-                        }
-                    }
                     self.grid.remove(coords);
                 },
+                ShipAction::TractorBeam => self.fire_tractor_beam(*coords),
                 ShipAction::Nothing => {}
             }
         }
     }
 
+    //fills the free cells between the ship and the player's row, a boss only holds one fighter at a time
+    fn fire_tractor_beam(&mut self, coords: Cords) {
+        let owner = match self.grid.get(&coords) {
+            Some(ship) if !ship.holds_captive() => ship.get_id(),
+            _ => return,
+        };
+
+        for row in (coords.0 + 1)..ROWS - 1 {
+            self.grid.entry(Cords(row, coords.1)).or_insert_with(|| Box::new(TractorBeamShip::new(owner)));
+        }
+    }
+
+    //removes every beam cell fired by `owner`
+    pub fn clear_tractor_beam(&mut self, owner: Uuid) {
+        self.grid.retain(|_, ship| !(ship.kind() == ShipKind::TractorBeam && ship.owner() == Some(owner)));
+    }
+
+    pub fn find_by_id(&mut self, id: Uuid) -> Option<&mut Box<dyn Ship>> {
+        self.grid.values_mut().find(|ship| ship.get_id() == id)
+    }
+
+    //moves the bodies after the ai has picked cells for this step and lets the free projectiles hit what they overlap
+    pub fn step_physics(&mut self, ctx: &AIContext, fighter_cells: &[Cords]) {
+        if let Some(mut physics) = self.physics.take() {
+            physics.step(self, ctx, fighter_cells);
+            self.physics = Some(physics);
+        }
+    }
+
+    //player bullets on the board, counting the ones flying free in physics mode
+    pub fn player_bullet_count(&self) -> usize {
+        let on_grid = self.grid.values().filter(|ship| ship.is_player_bullet()).count();
+        on_grid + self.physics.as_ref().map_or(0, |physics| physics.player_bullet_count())
+    }
+
+    fn clear_physics(&mut self) {
+        if let Some(physics) = &mut self.physics {
+            physics.clear();
+        }
+    }
+
     pub fn clear(&mut self) {
         self.grid.clear();
+        self.events.clear();
+        self.clear_physics();
     }
 }
@@ -1,41 +1,133 @@
 // ship_ai.rs
+use crate::level::{LevelRules, PLAYER_ROWS};
 use crate::settings::Settings;
 use crate::ship::Ship;
-use crate::structs::{Cords, RelCords, ShipAction, ROWS};
+use crate::structs::{Cords, RelCords, ShipAction, COLUMNS, ROWS};
+use rand::rngs::StdRng;
 use rand::Rng;
-use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+//how many jumps a script may take in one tick before it has to yield, stops empty loops from hanging the game
+const MAX_CONTROL_STEPS: usize = 64;
+const MAX_CALL_DEPTH: usize = 16;
 
 pub struct AIActionResult {
     pub move_on_to_next_action: bool,
     pub ship_action: ShipAction,
 }
 
+//what a ship can see about the game when it decides what to do this tick
+#[derive(Clone, Copy)]
+pub struct AIContext<'a> {
+    pub settings: &'a Settings,
+    pub dt: Duration,
+    pub player_position: Option<Cords>,
+    pub level: u32,
+    pub hit_points: u32,
+    //the ship's formation slot as the formation is right now
+    pub home: Option<Cords>,
+    //how fast waits run out on this level, 1.0 is normal speed
+    pub speed: f32,
+    pub rules: LevelRules,
+}
+
+pub enum ControlFlow {
+    Continue(usize),
+    Call(usize),
+    Return,
+}
 
 pub struct ShipAI {
     pub actions: Vec<AIAction>,
     pub action_index: usize,
+    call_stack: Vec<usize>,
+    //index of the jump back to the start after the main actions
+    main_end: usize,
+    laps: u32,
+    //the wait of the await the ai is on or last ran, how long its moves take at normal speed
+    interval: Option<Duration>,
 }
 
 impl ShipAI {
     pub fn new(actions: Vec<AIAction>) -> Self {
+        Self::with_subroutines(actions, Vec::new())
+    }
+
+    //links the actions into one flat list, If, Repeat, RandomChoice, Goto and Call become jumps and subroutines are placed after the main actions
+    pub fn with_subroutines(actions: Vec<AIAction>, subroutines: Vec<(String, Vec<AIAction>)>) -> Self {
+        let mut linker = Linker::default();
+        linker.emit_block(actions);
+        linker.resolve_gotos();
+        let main_end = linker.actions.len();
+        linker.actions.push(AIAction::Jump(0));
+
+        for (name, body) in subroutines {
+            linker.subroutines.insert(name, linker.actions.len());
+            linker.emit_block(body);
+            linker.resolve_gotos();
+            linker.actions.push(AIAction::Return);
+        }
+
         ShipAI {
-            actions,
+            actions: linker.resolve(),
             action_index: 0,
+            call_stack: Vec::new(),
+            main_end,
+            laps: 0,
+            interval: None,
         }
     }
 
+    //how many times the main actions have run to the end
+    pub fn laps(&self) -> u32 {
+        self.laps
+    }
+
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
     pub fn get_action(
         &mut self,
         cords: Cords,
         game_board: &HashMap<Cords, Box<dyn Ship>>,
-        settings: &Settings,
+        ctx: &AIContext,
+        rng: &mut StdRng,
     ) -> ShipAction {
         if self.actions.is_empty() {
             return ShipAction::Nothing;
         }
 
-        let result = self.actions[self.action_index].evaluate(cords, game_board, settings);
+        let mut control_steps = 0;
+        while let Some(flow) = self.actions[self.action_index].control_flow(self.action_index, cords, game_board, ctx, rng) {
+            if self.action_index == self.main_end {
+                self.laps += 1;
+            }
+
+            let next_index = match flow {
+                ControlFlow::Continue(next_index) => next_index,
+                ControlFlow::Call(target) if self.call_stack.len() < MAX_CALL_DEPTH => {
+                    self.call_stack.push(self.action_index + 1);
+                    target
+                }
+                ControlFlow::Call(_) => self.action_index + 1,
+                ControlFlow::Return => self.call_stack.pop().unwrap_or(0),
+            };
+            self.action_index = if next_index >= self.actions.len() { 0 } else { next_index };
+
+            control_steps += 1;
+            if control_steps >= MAX_CONTROL_STEPS {
+                return ShipAction::Nothing;
+            }
+        }
+
+        if let AIAction::AwaitAction(_, _, get_duration) = &self.actions[self.action_index] {
+            self.interval = Some(get_duration(ctx.settings));
+        }
+
+        let result = self.actions[self.action_index].evaluate(cords, game_board, ctx, rng);
         if result.move_on_to_next_action {
             if self.action_index == self.actions.len() - 1 {
                 self.action_index = 0;
@@ -47,14 +139,127 @@ impl ShipAI {
     }
 }
 
+#[derive(Default)]
+struct Linker {
+    actions: Vec<AIAction>,
+    labels: HashMap<String, usize>,
+    subroutines: HashMap<String, usize>,
+    gotos: Vec<(usize, String)>,
+    calls: Vec<(usize, String)>,
+}
+
+impl Linker {
+    fn emit_block(&mut self, actions: Vec<AIAction>) {
+        for action in actions {
+            self.emit(action);
+        }
+    }
+
+    fn emit(&mut self, action: AIAction) {
+        match action {
+            AIAction::If(condition, then, otherwise) => {
+                let branch_index = self.actions.len();
+                self.actions.push(AIAction::Branch(condition, 0));
+                self.emit_block(then);
+
+                if otherwise.is_empty() {
+                    self.set_target(branch_index, self.actions.len());
+                } else {
+                    let jump_index = self.actions.len();
+                    self.actions.push(AIAction::Jump(0));
+                    self.set_target(branch_index, self.actions.len());
+                    self.emit_block(otherwise);
+                    self.set_target(jump_index, self.actions.len());
+                }
+            }
+            AIAction::Repeat(times, body) => {
+                let start = self.actions.len();
+                self.emit_block(body);
+
+                let repeats = times.saturating_sub(1);
+                if repeats > 0 {
+                    self.actions.push(AIAction::Loop(repeats, repeats, start));
+                }
+            }
+            AIAction::RandomChoice(branches) => {
+                let choice_index = self.actions.len();
+                self.actions.push(AIAction::RandomJump(Vec::new()));
+
+                let mut targets = Vec::new();
+                let mut exit_jumps = Vec::new();
+                for branch in branches {
+                    targets.push(self.actions.len());
+                    self.emit_block(branch);
+                    exit_jumps.push(self.actions.len());
+                    self.actions.push(AIAction::Jump(0));
+                }
+
+                for jump_index in exit_jumps {
+                    self.set_target(jump_index, self.actions.len());
+                }
+                self.actions[choice_index] = AIAction::RandomJump(targets);
+            }
+            AIAction::Label(name) => {
+                self.labels.insert(name, self.actions.len());
+            }
+            AIAction::Goto(name) => {
+                self.gotos.push((self.actions.len(), name));
+                self.actions.push(AIAction::Jump(0));
+            }
+            AIAction::Call(name) => {
+                self.calls.push((self.actions.len(), name));
+                self.actions.push(AIAction::CallAt(0));
+            }
+            action => self.actions.push(action),
+        }
+    }
+
+    fn set_target(&mut self, index: usize, target: usize) {
+        match &mut self.actions[index] {
+            AIAction::Jump(jump_target) | AIAction::Branch(_, jump_target) | AIAction::CallAt(jump_target) => *jump_target = target,
+            _ => {}
+        }
+    }
+
+    //labels only reach within the body that declares them, so the gotos are resolved at the end of each one
+    fn resolve_gotos(&mut self) {
+        for (index, name) in std::mem::take(&mut self.gotos) {
+            let target = self.labels.get(&name).copied().unwrap_or(index + 1);
+            self.set_target(index, target);
+        }
+        self.labels.clear();
+    }
+
+    //unknown labels and subroutines just fall through to the next action
+    fn resolve(mut self) -> Vec<AIAction> {
+        for (index, name) in std::mem::take(&mut self.calls) {
+            match self.subroutines.get(&name).copied() {
+                Some(target) => self.set_target(index, target),
+                None => self.actions[index] = AIAction::Jump(index + 1),
+            }
+        }
+        self.actions
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Condition {
     ShipExists(Cords),
     PositionAvailable(RelCords),
     ShootPositionAvailable(RelCords),
+    PlayerInSameColumn,
+    PlayerWithinColumns(usize),
+    HitPointsBelow(u32),
+    LevelAtLeast(u32),
+    AtHome,
+    //percent chance to hold each time it is checked
+    Chance(u32),
+    Not(Box<Condition>),
 }
 
 impl Condition {
-    pub fn evaluate(&self, cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>) -> bool {
+    pub fn evaluate(&self, cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>, ctx: &AIContext, rng: &mut StdRng) -> bool {
         match self {
             Condition::ShipExists(ref target_cords) => {
                 game_board.contains_key(target_cords)
@@ -67,6 +272,17 @@ impl Condition {
                 let (target_cords, _) = rel_cords.evaluate(cords);
                 game_board.get(&target_cords).is_none()
             }
+            Condition::PlayerInSameColumn => {
+                ctx.player_position.is_some_and(|player| player.1 == cords.1)
+            }
+            Condition::PlayerWithinColumns(columns) => {
+                ctx.player_position.is_some_and(|player| player.1.abs_diff(cords.1) <= *columns)
+            }
+            Condition::HitPointsBelow(hit_points) => ctx.hit_points < *hit_points,
+            Condition::LevelAtLeast(level) => ctx.level >= *level,
+            Condition::AtHome => ctx.home.is_none_or(|home| home == cords),
+            Condition::Chance(percent) => rng.random_range(0..100) < *percent,
+            Condition::Not(condition) => !condition.evaluate(cords, game_board, ctx, rng),
         }
     }
 }
@@ -80,20 +296,161 @@ pub enum AIAction {
     MoveCautious(RelCords),
     ShootOrNothing,
     RelativeMove(RelCords),
-    AwaitAction(Box<AIAction>, Option<Instant>, Box<dyn Fn(&Settings) -> Duration>),
+    //one row down and one column toward the player, off the bottom it wraps back to the top
+    DiveTowardPlayer,
+    //one cell closer to the formation slot, coming down from the top after a dive
+    ReturnHome,
+    //one cell along the shortest way around other enemies to the formation slot, never wrapping or entering the player's rows
+    ApproachHome,
+    TractorBeam,
+    AwaitAction(Box<AIAction>, Duration, Box<dyn Fn(&Settings) -> Duration>),
+    If(Condition, Vec<AIAction>, Vec<AIAction>),
+    Repeat(u32, Vec<AIAction>),
+    RandomChoice(Vec<Vec<AIAction>>),
+    Label(String),
+    Goto(String),
+    Call(String),
+    //ShipAI::new turns the actions above into these, they pick the next action index without using up the tick
+    Jump(usize),
+    //falls through when the condition holds, otherwise jumps to the index
+    Branch(Condition, usize),
+    RandomJump(Vec<usize>),
+    //jumps back to the index while there are repeats left, then resets and falls through
+    Loop(u32, u32, usize),
+    CallAt(usize),
+    Return,
 }
 
 impl AIAction {
     pub fn new_await(action: AIAction, get_duration: impl Fn(&Settings) -> Duration  + 'static) -> Self {
-        AIAction::AwaitAction(Box::new(action), None, Box::new(get_duration))
+        AIAction::AwaitAction(Box::new(action), Duration::ZERO, Box::new(get_duration))
+    }
+
+    //enemies wait instead of running into each other, bullets are left to the grid to resolve
+    fn move_unless_blocked(cords: Cords, rel_cords: RelCords, game_board: &HashMap<Cords, Box<dyn Ship>>) -> AIActionResult {
+        let (new_cords, wrapped) = rel_cords.evaluate(cords);
+
+        AIActionResult {
+            move_on_to_next_action: true,
+            ship_action: if AIAction::is_blocked(new_cords, game_board) { ShipAction::Nothing } else { ShipAction::Move(new_cords, wrapped) },
+        }
+    }
+
+    //go around ships sitting in the way instead of waiting behind them forever
+    fn step_toward(cords: Cords, home: Cords, row_step: i32, game_board: &HashMap<Cords, Box<dyn Ship>>) -> AIActionResult {
+        let column_step = (home.1 as i32 - cords.1 as i32).signum();
+
+        let detour = [RelCords(row_step, column_step), RelCords(row_step, 0), RelCords(row_step, 1), RelCords(row_step, -1)]
+            .into_iter()
+            .filter(|rel_cords| *rel_cords != RelCords(0, 0))
+            .find(|rel_cords| !AIAction::is_blocked(rel_cords.evaluate(cords).0, game_board));
+
+        match detour {
+            Some(rel_cords) => AIAction::move_unless_blocked(cords, rel_cords, game_board),
+            None => AIActionResult {
+                move_on_to_next_action: true,
+                ship_action: ShipAction::Nothing,
+            },
+        }
+    }
+
+    //first cell of the shortest way from `cords` to `home`, None when other enemies wall it off
+    fn path_step(cords: Cords, home: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>) -> Option<Cords> {
+        let rows = ROWS - PLAYER_ROWS;
+        let mut came_from = HashMap::from([(cords, cords)]);
+        let mut queue = VecDeque::from([cords]);
+
+        while let Some(current) = queue.pop_front() {
+            if current == home {
+                let mut step = current;
+                while came_from[&step] != cords {
+                    step = came_from[&step];
+                }
+                return Some(step);
+            }
+
+            for (row, col) in [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)] {
+                let (row, col) = (current.0 as i32 + row, current.1 as i32 + col);
+                if row < 0 || col < 0 || row >= rows as i32 || col >= COLUMNS as i32 {
+                    continue;
+                }
+
+                let next = Cords(row as usize, col as usize);
+                if !came_from.contains_key(&next) && !AIAction::is_blocked(next, game_board) {
+                    came_from.insert(next, current);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn is_blocked(cords: Cords, game_board: &HashMap<Cords, Box<dyn Ship>>) -> bool {
+        game_board.get(&cords).is_some_and(|ship| ship.is_enemy())
+    }
+
+    //returns where to continue for control flow actions, None for actions that do something this tick
+    pub fn control_flow(
+        &mut self,
+        index: usize,
+        cords: Cords,
+        game_board: &HashMap<Cords, Box<dyn Ship>>,
+        ctx: &AIContext,
+        rng: &mut StdRng,
+    ) -> Option<ControlFlow> {
+        match self {
+            AIAction::Jump(target) => Some(ControlFlow::Continue(*target)),
+            AIAction::Branch(condition, else_target) => {
+                if condition.evaluate(cords, game_board, ctx, rng) {
+                    Some(ControlFlow::Continue(index + 1))
+                } else {
+                    Some(ControlFlow::Continue(*else_target))
+                }
+            }
+            AIAction::RandomJump(targets) => {
+                if targets.is_empty() {
+                    Some(ControlFlow::Continue(index + 1))
+                } else {
+                    Some(ControlFlow::Continue(targets[rng.random_range(0..targets.len())]))
+                }
+            }
+            AIAction::Loop(repeats, remaining, target) => {
+                if *remaining > 0 {
+                    *remaining -= 1;
+                    Some(ControlFlow::Continue(*target))
+                } else {
+                    *remaining = *repeats;
+                    Some(ControlFlow::Continue(index + 1))
+                }
+            }
+            AIAction::CallAt(target) => Some(ControlFlow::Call(*target)),
+            AIAction::Return => Some(ControlFlow::Return),
+            _ => None,
+        }
     }
 
     pub fn evaluate(
         &mut self,
         cords: Cords,
         game_board: &HashMap<Cords, Box<dyn Ship>>,
-        settings: &Settings,
+        ctx: &AIContext,
+        rng: &mut StdRng,
     ) -> AIActionResult {
+        let settings = ctx.settings;
+
+        let forbidden = match self {
+            AIAction::Shoot | AIAction::RandomShoot | AIAction::ShootOrNothing => ctx.rules.no_shooting,
+            AIAction::DiveTowardPlayer | AIAction::TractorBeam => ctx.rules.no_dives,
+            _ => false,
+        };
+        if forbidden {
+            return AIActionResult {
+                move_on_to_next_action: true,
+                ship_action: ShipAction::Nothing,
+            };
+        }
+
         match self {
             AIAction::Remove => AIActionResult {
                 move_on_to_next_action: true,
@@ -108,7 +465,6 @@ impl AIAction {
             AIAction::RandomShoot => {
                 let randomness = settings.value_stats.shooting_randomness;
                 if randomness > 0 {
-                    let mut rng = rand::thread_rng();
 
                     let threshold = match randomness {
                         1 => 90,
@@ -129,9 +485,7 @@ impl AIAction {
                     for row in (cords.0 + 1)..ROWS {
                         let check_coords = Cords(row, cords.1);
                         if let Some(ship) = game_board.get(&check_coords) {
-                            let ship_type = ship.display_type();
-                            if ship_type == "fly" || ship_type == "tiki_fly" ||
-                                ship_type == "northrop_fly" || ship_type == "b2_fly" {
+                            if ship.is_enemy() {
                                 can_shoot = false;
                                 break;
                             }
@@ -139,7 +493,7 @@ impl AIAction {
                     }
 
                     let condition = Condition::ShootPositionAvailable(RelCords(1, 0));
-                    if condition.evaluate(cords, game_board) && can_shoot && rng.gen_range(1..=100) <= threshold {
+                    if condition.evaluate(cords, game_board, ctx, rng) && can_shoot && rng.random_range(1..=100) <= threshold {
                         AIActionResult {
                             move_on_to_next_action: true,
                             ship_action: ShipAction::Shoot,
@@ -166,21 +520,15 @@ impl AIAction {
             AIAction::MoveCautious(rel_cords) => {
                 if settings.value_stats.fly_move {
                     let condition = Condition::PositionAvailable(rel_cords.clone());
-                    if condition.evaluate(cords, game_board) {
+                    if condition.evaluate(cords, game_board, ctx, rng) {
                         let (new_cords, wrap) = rel_cords.evaluate(cords);
 
                         let mut safe_to_move = true;
 
                         for (&check_coords, ship) in game_board.iter() {
-                            if check_coords == new_cords {
-                                let ship_type = ship.display_type();
-                                if ship_type == "fly" {
-                                    safe_to_move = false;
-                                    break;
-                                } else if ship_type == "bullet" {
-                                    safe_to_move = false;
-                                    break;
-                                }
+                            if check_coords == new_cords && (ship.is_enemy() || ship.is_projectile()) {
+                                safe_to_move = false;
+                                break;
                             }
                         }
                         if safe_to_move {
@@ -209,14 +557,45 @@ impl AIAction {
             },
             AIAction::RelativeMove(rel_cords) => {
                 let (new_cords, wrapped) = rel_cords.evaluate(cords);
-                if wrapped {
-                    println!("Wrapped: {}", wrapped);
-                }
                 AIActionResult {
                     move_on_to_next_action: true,
                     ship_action: ShipAction::Move(new_cords, wrapped),
                 }
             },
+            AIAction::DiveTowardPlayer => {
+                let column_step = ctx.player_position.map_or(0, |player| (player.1 as i32 - cords.1 as i32).signum());
+                AIAction::move_unless_blocked(cords, RelCords(1, column_step), game_board)
+            },
+            AIAction::ReturnHome => match ctx.home {
+                Some(home) if home != cords => {
+                    let row_step = if home.0 == cords.0 { 0 } else { 1 };
+                    AIAction::step_toward(cords, home, row_step, game_board)
+                }
+                _ => AIActionResult {
+                    move_on_to_next_action: true,
+                    ship_action: ShipAction::Nothing,
+                },
+            },
+            AIAction::ApproachHome => match ctx.home {
+                Some(home) if home != cords => match AIAction::path_step(cords, home, game_board) {
+                    Some(next) => AIActionResult {
+                        move_on_to_next_action: true,
+                        ship_action: ShipAction::Move(next, false),
+                    },
+                    None => {
+                        let row_step = (home.0 as i32 - cords.0 as i32).signum();
+                        AIAction::step_toward(cords, home, row_step, game_board)
+                    }
+                },
+                _ => AIActionResult {
+                    move_on_to_next_action: true,
+                    ship_action: ShipAction::Nothing,
+                },
+            },
+            AIAction::TractorBeam => AIActionResult {
+                move_on_to_next_action: true,
+                ship_action: ShipAction::TractorBeam,
+            },
             AIAction::ShootOrNothing => {
                 let mut can_shoot = true;
 
@@ -224,7 +603,7 @@ impl AIAction {
                     let check_coords = Cords(row, cords.1);
 
                     if let Some(ship) = game_board.get(&check_coords) {
-                        if ship.display_type() == "fly" {
+                        if ship.is_enemy() {
                             can_shoot = false;
                             break;
                         }
@@ -243,17 +622,13 @@ impl AIAction {
                     }
                 }
             },
-            AIAction::AwaitAction(ai_action, start_time, get_duration) => {
-                if start_time.is_none() {
-                    *start_time = Some(Instant::now());
-                }
-
-                let elapsed = start_time.unwrap().elapsed();
+            AIAction::AwaitAction(ai_action, waited, get_duration) => {
+                *waited += ctx.dt.mul_f32(ctx.speed);
 
-                if elapsed >= get_duration(&settings) {
-                    let result = ai_action.evaluate(cords, game_board, settings);
+                if *waited >= get_duration(&settings) {
+                    let result = ai_action.evaluate(cords, game_board, ctx, rng);
                     if result.move_on_to_next_action {
-                        *start_time = None;
+                        *waited = Duration::ZERO;
                     }
                     result
                 } else {
@@ -263,7 +638,19 @@ impl AIAction {
                     }
                 }
             },
-            AIAction::Nothing => AIActionResult {
+            AIAction::Nothing
+            | AIAction::If(_, _, _)
+            | AIAction::Repeat(_, _)
+            | AIAction::RandomChoice(_)
+            | AIAction::Label(_)
+            | AIAction::Goto(_)
+            | AIAction::Call(_)
+            | AIAction::Jump(_)
+            | AIAction::Branch(_, _)
+            | AIAction::RandomJump(_)
+            | AIAction::Loop(_, _, _)
+            | AIAction::CallAt(_)
+            | AIAction::Return => AIActionResult {
                 move_on_to_next_action: true,
                 ship_action: ShipAction::Nothing,
             },
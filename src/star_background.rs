@@ -1,5 +1,7 @@
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use rust_on_rails::prelude::*;
+use crate::structs::{stream_seed, RngStream};
 
 #[derive(Clone)]
 pub struct Star {
@@ -14,12 +16,13 @@ pub struct Star {
 pub struct StarBackground {
     stars: Vec<Star>,
     window_size: (u32, u32),
+    rng: StdRng,
 }
 
 impl StarBackground {
-    pub fn new(window_size: (u32, u32)) -> Self {
+    pub fn new(window_size: (u32, u32), seed: u64) -> Self {
         let mut stars = Vec::new();
-        let mut rng = rand::rng();
+        let mut rng = StdRng::seed_from_u64(stream_seed(seed, RngStream::Stars));
 
         for _ in 0..150 {
             let color = match rng.random_range(0..4) {
@@ -41,11 +44,12 @@ impl StarBackground {
         StarBackground {
             stars,
             window_size,
+            rng,
         }
     }
 
     pub fn on_tick(&mut self) {
-        let mut rng = rand::rng();
+        let rng = &mut self.rng;
         for star in &mut self.stars {
             star.y += star.speed;
 
@@ -1,4 +1,5 @@
-use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 pub const SIZE: usize = 10;
 pub const ROWS: usize = SIZE + 2;
@@ -10,10 +11,10 @@ pub const START_X: u32 = 5;
 pub const START_Y: u32 = 50;
 
 
-#[derive(Clone, Copy, Eq, Hash, PartialEq, Debug)]
+#[derive(Clone, Copy, Eq, Hash, PartialEq, Ord, PartialOrd, Debug, Serialize, Deserialize)]
 pub struct Cords(pub usize, pub usize);
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct RelCords(pub i32, pub i32);
 
 
@@ -52,18 +53,39 @@ impl RelCords {
     }
 }
 
+//each randomized subsystem gets its own stream so adding rolls in one doesn't shift the others
+#[derive(Clone, Copy, Debug)]
+pub enum RngStream {
+    Spawner,
+    ShipAI,
+    Stars,
+    Drops,
+}
+
+pub fn stream_seed(seed: u64, stream: RngStream) -> u64 {
+    seed ^ (stream as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+pub const EMPTY_FINGERPRINT: u64 = 0xcbf2_9ce4_8422_2325;
+
+//FNV-1a of `bytes` carried on from `hash`, unlike the std hasher it is the same in every build so replays can store it
+pub fn fingerprint(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
 #[derive(Clone, Debug)]
 pub enum ShipAction {
     Nothing,
     Remove,
     Shoot,
     Move(Cords, bool),
+    TractorBeam,
 }
 
 #[derive(Clone, Debug)]
 pub struct Timer {
 
-    last_action_time: Instant,
+    elapsed: Duration,
     interval_ms: u64,
 
     duration: u32,
@@ -75,7 +97,7 @@ pub struct Timer {
 impl Timer {
     pub fn new(interval_ms: u64) -> Self {
         Timer {
-            last_action_time: Instant::now(),
+            elapsed: Duration::ZERO,
             interval_ms,
             duration: 0,
             current: 0,
@@ -86,7 +108,7 @@ impl Timer {
 
     pub fn new_with_duration(interval_ms: u64, duration: u32, name: &str) -> Self {
         Timer {
-            last_action_time: Instant::now(),
+            elapsed: Duration::ZERO,
             interval_ms,
             duration,
             current: 0,
@@ -95,16 +117,16 @@ impl Timer {
         }
     }
 
-    pub fn tick(&mut self) -> bool {
+    //advances the timer by the game step `dt` instead of reading the wall clock
+    pub fn tick(&mut self, dt: Duration) -> bool {
         if !self.active {
             return false;
         }
 
-        let now = Instant::now();
-        let elapsed = now.duration_since(self.last_action_time).as_millis() as u64;
+        self.elapsed += dt;
 
-        if elapsed >= self.interval_ms {
-            self.last_action_time = now;
+        if self.elapsed >= Duration::from_millis(self.interval_ms) {
+            self.elapsed = Duration::ZERO;
 
             self.current += 1;
 
@@ -137,7 +159,7 @@ impl Timer {
     pub fn start(&mut self) {
         self.current = 0;
         self.active = true;
-        self.last_action_time = Instant::now();
+        self.elapsed = Duration::ZERO;
     }
 
     pub fn stop(&mut self) {
@@ -147,7 +169,7 @@ impl Timer {
     pub fn reset(&mut self) {
         self.current = 0;
         self.active = true;
-        self.last_action_time = Instant::now();
+        self.elapsed = Duration::ZERO;
     }
 
     pub fn is_active(&self) -> bool {
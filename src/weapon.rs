@@ -0,0 +1,186 @@
+use crate::ship::ProjectileKind;
+use crate::structs::{fingerprint, Cords, COLUMNS, EMPTY_FINGERPRINT};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::time::Duration;
+
+const BUILTIN_WEAPONS: &str = include_str!("../assets/weapons.json");
+
+//how the bullets of one shot are laid out
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpreadPattern {
+    //one behind the other above the fighter
+    Column,
+    //side by side in the columns around the fighter
+    Wide,
+}
+
+//how the player's fighter shoots, as declared in weapons.json
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Weapon {
+    pub name: String,
+    pub cooldown_ms: u64,
+    pub bullets_per_shot: u32,
+    pub spread: SpreadPattern,
+    pub projectile: ProjectileKind,
+    //time a bullet takes to fly one row, None follows the laser speed setting
+    pub bullet_speed_ms: Option<u64>,
+    //player bullets allowed on the board at once, 0 for no limit
+    pub max_bullets: u32,
+}
+
+//the arcade rule, one bullet per shot and never more than two on the screen
+impl Default for Weapon {
+    fn default() -> Self {
+        Weapon {
+            name: String::from("classic"),
+            cooldown_ms: 200,
+            bullets_per_shot: 1,
+            spread: SpreadPattern::Column,
+            projectile: ProjectileKind::Bullet,
+            bullet_speed_ms: None,
+            max_bullets: 2,
+        }
+    }
+}
+
+impl Weapon {
+    pub fn cooldown(&self) -> Duration {
+        Duration::from_millis(self.cooldown_ms)
+    }
+
+    pub fn bullet_speed(&self) -> Option<Duration> {
+        self.bullet_speed_ms.map(Duration::from_millis)
+    }
+
+    //how many more bullets fit on the board when `on_screen` are already flying
+    pub fn bullets_left(&self, on_screen: usize) -> usize {
+        match self.max_bullets {
+            0 => usize::MAX,
+            max => (max as usize).saturating_sub(on_screen),
+        }
+    }
+
+    //the cells one shot puts bullets in when fired from `pos`, nearest the fighter first
+    pub fn bullet_cells(&self, pos: Cords) -> Vec<Cords> {
+        let count = self.bullets_per_shot as usize;
+        match self.spread {
+            SpreadPattern::Column => (1..=count.min(pos.0))
+                .filter_map(|distance| pos.0.checked_sub(distance).map(|row| Cords(row, pos.1)))
+                .collect(),
+            SpreadPattern::Wide => {
+                let Some(row) = pos.0.checked_sub(1) else {
+                    return Vec::new();
+                };
+                let first = pos.1 as i32 - (count as i32 - 1) / 2;
+                (first..first + count as i32)
+                    .filter(|&col| col >= 0 && col < COLUMNS as i32)
+                    .map(|col| Cords(row, col as usize))
+                    .collect()
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err(String::from("weapon has no name"));
+        }
+        if self.bullets_per_shot == 0 {
+            return Err(format!("weapon `{}` fires no bullets", self.name));
+        }
+        // a shot never needs more bullets than the board is wide
+        if self.bullets_per_shot as usize > COLUMNS {
+            return Err(format!("weapon `{}` fires more than {} bullets per shot", self.name, COLUMNS));
+        }
+        if self.bullet_speed_ms == Some(0) {
+            return Err(format!("weapon `{}` has a bullet speed of 0 ms", self.name));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WeaponFile {
+    weapons: Vec<Weapon>,
+}
+
+#[derive(Debug)]
+pub enum WeaponError {
+    Io(String, std::io::Error),
+    Parse(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for WeaponError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WeaponError::Io(path, err) => write!(f, "{}: {}", path, err),
+            WeaponError::Parse(err) => write!(f, "line {}: {}", err.line(), err),
+            WeaponError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+//the weapons that can be picked by name, the first one is what the player starts with
+#[derive(Debug, Clone)]
+pub struct WeaponLibrary {
+    weapons: Vec<Weapon>,
+    fingerprint: u64,
+}
+
+impl WeaponLibrary {
+    //the weapons shipped in assets/weapons.json
+    pub fn builtin() -> Self {
+        Self::from_json(BUILTIN_WEAPONS).expect("assets/weapons.json is invalid")
+    }
+
+    pub fn load(path: &str) -> Result<Self, WeaponError> {
+        let contents = fs::read_to_string(path).map_err(|err| WeaponError::Io(path.to_string(), err))?;
+        Self::from_json(&contents)
+    }
+
+    pub fn from_json(contents: &str) -> Result<Self, WeaponError> {
+        let file: WeaponFile = serde_json::from_str(contents).map_err(WeaponError::Parse)?;
+
+        if file.weapons.is_empty() {
+            return Err(WeaponError::Invalid(String::from("no weapons declared")));
+        }
+
+        let mut names = HashSet::new();
+        for weapon in &file.weapons {
+            weapon.validate().map_err(WeaponError::Invalid)?;
+            if !names.insert(weapon.name.as_str()) {
+                return Err(WeaponError::Invalid(format!("weapon `{}` is declared twice", weapon.name)));
+            }
+        }
+
+        Ok(WeaponLibrary { weapons: file.weapons, fingerprint: fingerprint(EMPTY_FINGERPRINT, contents.as_bytes()) })
+    }
+
+    //changes whenever the weapons file does
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    pub fn default_weapon(&self) -> &Weapon {
+        &self.weapons[0]
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Weapon> {
+        self.weapons.iter().find(|weapon| weapon.name == name)
+    }
+
+    pub fn all(&self) -> &[Weapon] {
+        &self.weapons
+    }
+
+    //the weapon after `name` in the file, wrapping around, used to cycle through them from the settings
+    pub fn next_after(&self, name: &str) -> &Weapon {
+        let index = self.weapons.iter().position(|weapon| weapon.name == name).map_or(0, |index| index + 1);
+        &self.weapons[index % self.weapons.len()]
+    }
+}
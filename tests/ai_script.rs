@@ -0,0 +1,42 @@
+use main::ai_script::{AIScriptError, AIScriptLibrary};
+
+fn invalid_message(contents: &str) -> String {
+    match AIScriptLibrary::from_json(contents) {
+        Err(AIScriptError::Invalid { message, .. }) => message,
+        Err(err) => panic!("expected an invalid script, got {}", err),
+        Ok(_) => panic!("expected an invalid script"),
+    }
+}
+
+#[test]
+fn goto_cannot_reach_a_label_in_another_body() {
+    let message = invalid_message(r#"{
+        "scripts": { "idle": [{ "goto": "loop" }] },
+        "subroutines": { "spin": [{ "label": "loop" }, { "goto": "loop" }] }
+    }"#);
+    assert_eq!(message, "goto to unknown label `loop` in script `idle`");
+}
+
+#[test]
+fn label_declared_twice_is_rejected() {
+    let message = invalid_message(r#"{
+        "scripts": { "idle": [{ "label": "top" }, "shoot", { "repeat": { "times": 2, "steps": [{ "label": "top" }] } }] }
+    }"#);
+    assert_eq!(message, "label `top` is declared twice in script `idle`");
+}
+
+#[test]
+fn the_same_label_can_be_used_in_separate_bodies() {
+    AIScriptLibrary::from_json(r#"{
+        "scripts": { "idle": [{ "label": "top" }, "nothing", { "goto": "top" }, { "call": "spin" }] },
+        "subroutines": { "spin": [{ "label": "top" }, "shoot", { "goto": "top" }] }
+    }"#).expect("labels are per body");
+}
+
+#[test]
+fn the_first_broken_body_by_name_is_reported() {
+    let message = invalid_message(r#"{
+        "scripts": { "b": [{ "call": "missing_b" }], "a": [{ "call": "missing_a" }] }
+    }"#);
+    assert_eq!(message, "call to unknown subroutine `missing_a` in script `a`");
+}
@@ -0,0 +1,159 @@
+use main::ai_script::AIScriptLibrary;
+use main::enemy::EnemyCatalog;
+use main::game_state::{GameInput, GameMode, GameState};
+use main::level::LevelLibrary;
+use main::settings::Settings;
+use main::power_up::{new_power_up_ship, PowerUpKind};
+use main::ship::{new_enemy_bullet, GridEvent};
+use main::structs::{Cords, COLUMNS};
+use main::weapon::WeaponLibrary;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+const STEP: Duration = Duration::from_millis(16);
+
+fn new_game(seed: u64, mode: GameMode) -> GameState {
+    let enemies = Arc::new(EnemyCatalog::builtin(&AIScriptLibrary::builtin()));
+    let levels = Arc::new(LevelLibrary::builtin(&enemies));
+    GameState::new(Settings::new(), seed, enemies, levels, Arc::new(WeaponLibrary::builtin()), mode)
+}
+
+//sweeps the fighter back and forth while holding the shoot pad
+fn scripted_inputs(step: u64) -> Vec<GameInput> {
+    let mut inputs = vec![GameInput::Shoot];
+    match (step / 40) % 4 {
+        0 | 1 => inputs.push(GameInput::MoveLeft),
+        _ => inputs.push(GameInput::MoveRight),
+    }
+    inputs
+}
+
+//what a game looks like after `steps`, the board sorted so it can be compared
+fn play(seed: u64, mode: GameMode, steps: u64) -> (u32, u8, Vec<(Cords, String)>) {
+    let mut game = new_game(seed, mode);
+    for step in 0..steps {
+        game.step(STEP, &scripted_inputs(step));
+    }
+
+    let mut board: Vec<(Cords, String)> = game.ship_grid.grid
+        .iter()
+        .map(|(&cords, ship)| (cords, format!("{:?}", ship.kind())))
+        .collect();
+    board.sort();
+
+    (game.score(), game.player.lives, board)
+}
+
+#[test]
+fn same_seed_and_inputs_play_out_the_same() {
+    for mode in [GameMode::Grid, GameMode::Physics] {
+        let first = play(7, mode, 6000);
+        let second = play(7, mode, 6000);
+        assert_eq!(first, second, "{:?} mode diverged", mode);
+        assert!(first.0 > 0, "{:?} mode never scored", mode);
+    }
+}
+
+#[test]
+fn game_steps_without_a_window() {
+    let mut game = new_game(3, GameMode::Grid);
+    for step in 0..600 {
+        game.step(STEP, &scripted_inputs(step));
+    }
+
+    assert_eq!(game.steps(), 600);
+    assert_eq!(game.elapsed(), STEP * 600);
+    assert!(!game.ship_grid.grid.is_empty());
+}
+
+#[test]
+fn hitting_both_fighters_in_one_step_only_loses_the_second_fighter() {
+    let mut game = new_game(5, GameMode::Grid);
+    game.step(STEP, &[]);
+
+    let pos = game.player.current_position.expect("fighter on the board");
+    let pos = Cords(pos.0, pos.1.min(COLUMNS - 2));
+    game.player.current_position = Some(pos);
+    game.player.dual_fighter = true;
+    let lives = game.player.lives;
+
+    game.ship_grid.grid.insert(pos, new_enemy_bullet(Uuid::new_v4()));
+    game.ship_grid.grid.insert(Cords(pos.0, pos.1 + 1), new_enemy_bullet(Uuid::new_v4()));
+    game.step(STEP, &[]);
+
+    assert!(!game.player.dual_fighter);
+    assert_eq!(game.player.lives, lives);
+}
+
+#[test]
+fn hitting_the_left_fighter_keeps_the_right_one_in_its_cell() {
+    let mut game = new_game(5, GameMode::Grid);
+    game.step(STEP, &[]);
+
+    let pos = game.player.current_position.expect("fighter on the board");
+    let pos = Cords(pos.0, pos.1.min(COLUMNS - 2));
+    game.player.current_position = Some(pos);
+    game.player.dual_fighter = true;
+    let lives = game.player.lives;
+
+    game.ship_grid.grid.insert(pos, new_enemy_bullet(Uuid::new_v4()));
+    game.step(STEP, &[]);
+
+    assert!(!game.player.dual_fighter);
+    assert_eq!(game.player.lives, lives);
+    assert_eq!(game.player.current_position, Some(Cords(pos.0, pos.1 + 1)));
+}
+
+//the first move `ship_id` makes, stepping the game until it happens
+fn first_move(game: &mut GameState, ship_id: Uuid) -> Duration {
+    for _ in 0..200 {
+        game.step(STEP, &[]);
+        let moved = game.events().iter().find_map(|event| match event {
+            GridEvent::ShipMoved { id, interval, .. } if *id == ship_id => Some(*interval),
+            _ => None,
+        });
+        if let Some(interval) = moved {
+            return interval;
+        }
+    }
+    panic!("the ship never moved");
+}
+
+#[test]
+fn moves_report_how_long_the_ship_takes() {
+    let mut game = new_game(9, GameMode::Grid);
+    game.step(STEP, &[]);
+
+    let drop = new_power_up_ship(PowerUpKind::Shield);
+    let drop_id = drop.get_id();
+    game.ship_grid.grid.insert(Cords(2, 0), drop);
+    assert_eq!(first_move(&mut game, drop_id), Duration::from_millis(250));
+
+    let bullet = new_enemy_bullet(Uuid::new_v4());
+    let bullet_id = bullet.get_id();
+    game.ship_grid.grid.insert(Cords(2, COLUMNS - 1), bullet);
+    assert_eq!(first_move(&mut game, bullet_id), game.settings.value_stats.laser_speed);
+}
+
+#[test]
+fn physics_fighter_touches_what_its_hitbox_overlaps() {
+    let mut game = new_game(11, GameMode::Physics);
+    game.step(STEP, &[]);
+    let pos = game.player.current_position.expect("fighter on the board");
+    let aside = Cords(pos.0, if pos.1 >= 2 { pos.1 - 2 } else { pos.1 + 2 });
+
+    game.ship_grid.grid.insert(aside, new_power_up_ship(PowerUpKind::Shield));
+    game.step(STEP, &[]);
+
+    // the drop's cell is the fighter's from now on, its body still has to swing over before it is picked up
+    let drop = game.ship_grid.grid.remove(&aside).expect("drop still falling");
+    game.ship_grid.grid.insert(pos, drop);
+    game.step(STEP, &[]);
+    assert!(!game.player.power_ups.is_active(PowerUpKind::Shield));
+
+    for _ in 0..10 {
+        game.step(STEP, &[]);
+    }
+    assert!(game.player.power_ups.is_active(PowerUpKind::Shield));
+}
@@ -0,0 +1,59 @@
+use main::ai_script::AIScriptLibrary;
+use main::enemy::EnemyCatalog;
+use main::level::LevelLibrary;
+use main::replay::{Replay, ReplayError};
+use main::structs::Cords;
+use main::weapon::WeaponLibrary;
+use std::sync::Arc;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/collisions.jsonl");
+
+fn builtin_content() -> (Arc<EnemyCatalog>, Arc<LevelLibrary>, Arc<WeaponLibrary>) {
+    let enemies = Arc::new(EnemyCatalog::builtin(&AIScriptLibrary::builtin()));
+    let levels = Arc::new(LevelLibrary::builtin(&enemies));
+    (enemies, levels, Arc::new(WeaponLibrary::builtin()))
+}
+
+fn golden_board(cells: &[((usize, usize), &str)]) -> Vec<(Cords, String)> {
+    cells.iter().map(|&((row, col), kind)| (Cords(row, col), kind.to_string())).collect()
+}
+
+//the fixture is 1800 steps of a fighter sweeping the board and shooting, recorded against the builtin content
+#[test]
+fn fixture_plays_back_to_the_recorded_game() {
+    let replay = Replay::load(FIXTURE).expect("fixture loads");
+    let (enemies, levels, weapons) = builtin_content();
+    replay.check_content(&enemies, &levels, &weapons).expect("fixture matches the builtin content");
+
+    let game = replay.simulate(enemies, levels, weapons, 1800);
+    let mut board: Vec<(Cords, String)> = game.ship_grid.grid
+        .iter()
+        .map(|(&cords, ship)| (cords, format!("{:?}", ship.kind())))
+        .collect();
+    board.sort();
+
+    assert_eq!(game.score(), 1450);
+    assert_eq!(game.player.lives, 3);
+    assert_eq!(board, golden_board(&[
+        ((0, 0), "Enemy(EnemyId(0))"),
+        ((0, 8), "Projectile(Bullet)"),
+        ((0, 11), "Enemy(EnemyId(1))"),
+        ((1, 1), "Enemy(EnemyId(1))"),
+        ((1, 7), "Projectile(Bullet)"),
+        ((2, 13), "Enemy(EnemyId(1))"),
+        ((3, 0), "Enemy(EnemyId(0))"),
+        ((4, 1), "Enemy(EnemyId(0))"),
+    ]));
+}
+
+#[test]
+fn replay_refuses_other_content() {
+    let mut replay = Replay::load(FIXTURE).expect("fixture loads");
+    replay.header.content ^= 1;
+    let (enemies, levels, weapons) = builtin_content();
+
+    assert!(matches!(
+        replay.check_content(&enemies, &levels, &weapons),
+        Err(ReplayError::ContentMismatch { .. })
+    ));
+}
@@ -0,0 +1,28 @@
+use main::structs::{Cords, COLUMNS};
+use main::weapon::{SpreadPattern, Weapon};
+
+#[test]
+fn more_bullets_than_the_board_is_wide_are_refused() {
+    let weapon = Weapon {
+        name: String::from("flood"),
+        bullets_per_shot: COLUMNS as u32 + 1,
+        ..Weapon::default()
+    };
+    assert!(weapon.validate().is_err());
+
+    let weapon = Weapon {
+        bullets_per_shot: COLUMNS as u32,
+        spread: SpreadPattern::Wide,
+        ..weapon
+    };
+    assert!(weapon.validate().is_ok());
+}
+
+#[test]
+fn a_column_shot_stops_at_the_top_row() {
+    let weapon = Weapon {
+        bullets_per_shot: 5,
+        ..Weapon::default()
+    };
+    assert_eq!(weapon.bullet_cells(Cords(2, 4)), vec![Cords(1, 4), Cords(0, 4)]);
+}